@@ -1,14 +1,70 @@
 #[macro_use]
 extern crate serde;
-use candid::{Decode, Encode};
-use ic_cdk::api::time;
+use candid::{Decode, Principal};
+use chrono::{Datelike, NaiveDate};
 use ic_stable_structures::memory_manager::{MemoryId, MemoryManager, VirtualMemory};
-use ic_stable_structures::{BoundedStorable, Cell, DefaultMemoryImpl, StableBTreeMap, Storable};
-use std::{borrow::Cow, cell::RefCell};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::{Cell, DefaultMemoryImpl, StableBTreeMap, StableLog, Storable};
+use std::ops::{Bound as RangeBound, RangeInclusive};
+use std::{borrow::Cow, cell::RefCell, thread::LocalKey};
 use std::collections::HashMap;
 
 type Memory = VirtualMemory<DefaultMemoryImpl>;
 type IdCell = Cell<u64, Memory>;
+type Index = StableBTreeMap<IndexKey, (), Memory>;
+
+use system::{caller, is_controller, time};
+
+// The system API calls the canister makes. Unit tests run outside a canister,
+// so there they read a clock and a caller the test sets instead.
+#[cfg(not(test))]
+mod system {
+    use candid::Principal;
+
+    pub fn time() -> u64 {
+        ic_cdk::api::time()
+    }
+
+    pub fn caller() -> Principal {
+        ic_cdk::caller()
+    }
+
+    pub fn is_controller(principal: &Principal) -> bool {
+        ic_cdk::api::is_controller(principal)
+    }
+}
+
+#[cfg(test)]
+mod system {
+    use candid::Principal;
+    use std::cell::Cell;
+
+    thread_local! {
+        static NOW: Cell<u64> = const { Cell::new(0) };
+        static CALLER: Cell<Principal> = const { Cell::new(Principal::anonymous()) };
+        static CONTROLLER: Cell<Option<Principal>> = const { Cell::new(None) };
+    }
+
+    pub fn time() -> u64 {
+        NOW.with(Cell::get)
+    }
+
+    pub fn caller() -> Principal {
+        CALLER.with(Cell::get)
+    }
+
+    pub fn is_controller(principal: &Principal) -> bool {
+        CONTROLLER.with(|controller| controller.get() == Some(*principal))
+    }
+
+    pub fn set_caller(principal: Principal) {
+        CALLER.with(|cell| cell.set(principal));
+    }
+
+    pub fn set_controller(principal: Principal) {
+        CONTROLLER.with(|cell| cell.set(Some(principal)));
+    }
+}
 
 // Airstrip struct
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
@@ -16,699 +72,9096 @@ struct Airstrip {
     id: u64,
     name: String,
     location: String,
+    coordinates: Option<Coordinates>,
     contact: String,
     email: String,
-    runway_length: u64, // in meters
-    capacity: u64,      // maximum number of planes
+    runway_length: u64,      // in meters
+    capacity: u64,           // maximum number of planes, i.e. parking stands
+    movements_per_hour: u64, // arrivals plus departures per clock hour, 0 = unlimited
     created_at: u64,
+    archived_at: Option<u64>,
 }
 
-// Flight struct
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct Flight {
-    id: u64,
-    airstrip_id: u64,
-    flight_number: String,
-    destination: String,
-    departure_time: u64,
-    arrival_time: u64,
-    status: String, // "scheduled", "delayed", "completed"
+// Coordinates struct
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default)]
+struct Coordinates {
+    latitude: f64,  // decimal degrees, north positive
+    longitude: f64, // decimal degrees, east positive
+    elevation: i64, // in meters above mean sea level
 }
 
-// Pilot struct
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct Pilot {
-    id: u64,
-    name: String,
-    license_number: String,
-    experience_years: u64,
-    contact: String,
-    email: String,
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+impl Coordinates {
+    fn validate(&self) -> Result<(), Message> {
+        if !self.latitude.is_finite() || !(-90.0..=90.0).contains(&self.latitude) {
+            return Err(Message::InvalidPayload("Latitude must be between -90 and 90 degrees".to_string()));
+        }
+        if !self.longitude.is_finite() || !(-180.0..=180.0).contains(&self.longitude) {
+            return Err(Message::InvalidPayload("Longitude must be between -180 and 180 degrees".to_string()));
+        }
+        Ok(())
+    }
+
+    // Great-circle distance using the haversine formula
+    fn distance_km(&self, latitude: f64, longitude: f64) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().min(1.0).asin()
+    }
 }
 
-// PilotSchedule struct
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct PilotSchedule {
-    id: u64,
-    pilot_id: u64,
-    flight_id: u64,
-    start_time: u64,
-    end_time: u64,
-    status: String, // "scheduled", "completed", "cancelled"
+// AirstripDistance struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct AirstripDistance {
+    airstrip: Airstrip,
+    distance_km: f64,
 }
 
-// EmergencyProtocol struct
+// Runway struct
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct EmergencyProtocol {
+struct Runway {
     id: u64,
     airstrip_id: u64,
-    protocol_type: String, // "weather", "technical", "security", "medical"
-    description: String,
-    contact_numbers: Vec<String>,
-    evacuation_routes: Vec<String>,
+    designator: String, // e.g. "09/27"
+    heading: u16,       // magnetic heading of the primary end, in degrees
+    surface: RunwaySurface,
+    width: u64, // in meters
+    lighting: RunwayLighting,
+    declared_distances: DeclaredDistances,
     created_at: u64,
 }
 
-// FuelInventory struct
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct FuelInventory {
-    id: u64,
-    airstrip_id: u64,
-    fuel_type: String,
-    quantity: f64,
-    unit_price: f64,
-    last_updated: u64,
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum RunwaySurface {
+    #[default]
+    Asphalt,
+    Concrete,
+    Gravel,
+    Laterite,
+    Grass,
+    Dirt,
 }
 
-// Revenue struct
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum RunwayLighting {
+    #[default]
+    Unlit,
+    LowIntensity,
+    MediumIntensity,
+    HighIntensity,
+}
+
+// DeclaredDistances struct, all in meters
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default)]
+struct DeclaredDistances {
+    tora: u64, // take-off run available
+    toda: u64, // take-off distance available
+    asda: u64, // accelerate-stop distance available
+    lda: u64,  // landing distance available
+}
+
+impl DeclaredDistances {
+    fn validate(&self) -> Result<(), Message> {
+        if self.tora == 0 || self.lda == 0 {
+            return Err(Message::InvalidPayload("TORA and LDA must be greater than zero".to_string()));
+        }
+        if self.toda < self.tora || self.asda < self.tora {
+            return Err(Message::InvalidPayload("TODA and ASDA cannot be shorter than TORA".to_string()));
+        }
+        Ok(())
+    }
+}
+
+// Stand struct: a named parking position on the apron
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct Revenue {
+struct Stand {
     id: u64,
     airstrip_id: u64,
-    source: String, // "landing_fees", "fuel_sales", "parking", "maintenance"
-    amount: f64,
-    transaction_date: u64,
-    description: String,
+    name: String,
+    size_class: SizeClass, // largest aircraft the stand can hold
+    has_fuel: bool,
+    has_power: bool,
+    created_at: u64,
 }
 
-// CapacityStatus struct
-#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
-struct CapacityStatus {
-    total_capacity: u64,
-    current_occupancy: u64,
-    scheduled_arrivals: u64,
-    scheduled_departures: u64,
-    available_slots: u64,
+// SizeClass enum, ordered from smallest to largest
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum SizeClass {
+    #[default]
+    Light, // MTOW up to 5,700 kg
+    Medium, // MTOW up to 27,000 kg
+    Heavy,
 }
 
-// MaintenanceSchedule struct
+// StandAssignment struct
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct MaintenanceSchedule {
+struct StandAssignment {
     id: u64,
+    stand_id: u64,
     airstrip_id: u64,
-    date: u64,
-    description: String,
-    status: String, // "scheduled", "completed"
+    flight_id: u64,
+    aircraft_id: u64,
+    start_time: u64,
+    end_time: u64,
+    assigned_manually: bool,
+    created_at: u64,
+    released_at: Option<u64>,
 }
 
-// Payload structs
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct CreateAirstripPayload {
-    name: String,
-    location: String,
-    contact: String,
-    email: String,
-    runway_length: u64,
-    capacity: u64,
+// StandOccupancy struct: who holds a stand over a queried time range
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct StandOccupancy {
+    stand: Stand,
+    holdings: Vec<StandHolding>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct StandHolding {
+    assignment_id: u64,
+    flight_id: u64,
+    flight_number: String,
+    aircraft_id: u64,
+    registration: String,
+    start_time: u64,
+    end_time: u64,
 }
 
+// Flight struct
 #[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct ScheduleFlightPayload {
+struct Flight {
+    id: u64,
     airstrip_id: u64,
     flight_number: String,
     destination: String,
     departure_time: u64,
     arrival_time: u64,
+    aircraft_id: u64,
+    runway_id: Option<u64>,
+    movement: MovementType,
+    ground_time_minutes: u64, // time spent on a stand at this airstrip
+    slot_id: Option<u64>,
+    stand_assignment_id: Option<u64>,
+    status: FlightStatus,
+    status_history: Vec<FlightStatusChange>,
+    archived_at: Option<u64>,
+    #[serde(default)]
+    category: FlightCategory,
 }
 
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct RegisterPilotPayload {
-    name: String,
-    license_number: String,
-    experience_years: u64,
-    contact: String,
-    email: String,
+// FlightCategory enum: what the flight is for, which drives its crew complement
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum FlightCategory {
+    #[default]
+    Passenger,
+    Charter,
+    Cargo,
+    Medevac,
+    Training,
 }
 
-#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
-struct ScheduleMaintenancePayload {
-    airstrip_id: u64,
-    date: u64,
-    description: String,
-}
+impl Flight {
+    // Time of the movement that uses this airstrip's runway
+    fn movement_time(&self) -> u64 {
+        match self.movement {
+            MovementType::Arrival => self.arrival_time,
+            MovementType::Departure => self.departure_time,
+        }
+    }
 
-#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
-enum Message {
-    Success(String),
-    Error(String),
-    NotFound(String),
-    InvalidPayload(String),
-}
+    // Half-open window [start, end) during which the movement needs the runway
+    fn runway_window(&self) -> (u64, u64) {
+        let slot = RUNWAY_SLOT_MINUTES * NANOS_PER_MINUTE;
+        let movement = self.movement_time();
+        (movement.saturating_sub(slot), movement.saturating_add(slot))
+    }
 
-// Implementing Storable for Airstrip
-impl Storable for Airstrip {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+    // Half-open window [start, end) during which the aircraft holds a stand here
+    fn ground_window(&self) -> (u64, u64) {
+        let ground_time = self.ground_time_minutes.saturating_mul(NANOS_PER_MINUTE);
+        match self.movement {
+            MovementType::Arrival => (self.arrival_time, self.arrival_time.saturating_add(ground_time)),
+            MovementType::Departure => (self.departure_time.saturating_sub(ground_time), self.departure_time),
+        }
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+    fn occupies_capacity(&self) -> bool {
+        self.archived_at.is_none() && self.status != FlightStatus::Cancelled
     }
-}
 
-impl BoundedStorable for Airstrip {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+    // Live and not yet departed, so crew can still be changed
+    fn awaiting_departure(&self) -> bool {
+        self.archived_at.is_none()
+            && matches!(self.status, FlightStatus::Scheduled | FlightStatus::Delayed | FlightStatus::Boarding)
+    }
 }
 
-// Implementing Storable for Flight
-impl Storable for Flight {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+const NANOS_PER_MINUTE: u64 = 60_000_000_000;
+const NANOS_PER_HOUR: u64 = 60 * NANOS_PER_MINUTE;
+const MAX_OCCUPANCY_WINDOW_HOURS: u64 = 7 * 24;
+// Longest ground time a flight may book; also bounds how far back index scans
+// must look for ground windows that overlap a given time
+const MAX_GROUND_TIME_MINUTES: u64 = 7 * 24 * 60;
+const MAX_GROUND_TIME: u64 = MAX_GROUND_TIME_MINUTES * NANOS_PER_MINUTE;
+// Runway time either side of a movement, for taxi, line-up and roll-out
+const RUNWAY_SLOT_MINUTES: u64 = 15;
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// MovementType enum: whether the flight lands at or leaves from its airstrip
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum MovementType {
+    Arrival,
+    #[default]
+    Departure,
 }
 
-impl BoundedStorable for Flight {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// SlotReservation struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SlotReservation {
+    id: u64,
+    airstrip_id: u64,
+    flight_id: u64,
+    movement: MovementType,
+    slot_start: u64, // start of the clock hour the movement falls in
+    movement_time: u64,
+    created_at: u64,
+    released_at: Option<u64>,
 }
 
-// Implementing Storable for Pilot
-impl Storable for Pilot {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// FlightStatus enum
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum FlightStatus {
+    #[default]
+    Scheduled,
+    Boarding,
+    Departed,
+    Airborne,
+    Arrived,
+    Delayed,
+    Diverted,
+    Cancelled,
+    Completed,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+impl FlightStatus {
+    // Legal moves through the flight lifecycle; Completed and Cancelled are final
+    fn can_transition_to(self, next: FlightStatus) -> bool {
+        use FlightStatus::*;
+        matches!(
+            (self, next),
+            (Scheduled, Boarding | Delayed | Cancelled)
+                | (Delayed, Scheduled | Boarding | Cancelled)
+                | (Boarding, Departed | Delayed | Cancelled)
+                | (Departed, Airborne)
+                | (Airborne, Arrived | Diverted)
+                | (Diverted, Arrived)
+                | (Arrived, Completed)
+        )
     }
 }
 
-impl BoundedStorable for Pilot {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// FlightStatusChange struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct FlightStatusChange {
+    status: FlightStatus,
+    changed_at: u64,
 }
 
-// Implementing Storable for PilotSchedule
-impl Storable for PilotSchedule {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+// Aircraft struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Aircraft {
+    id: u64,
+    registration: String, // tail number, e.g. "5Y-ABC"
+    aircraft_type: String,
+    mtow: u64, // maximum take-off weight in kg
+    seat_count: u64,
+    takeoff_distance_required: u64, // in meters
+    landing_distance_required: u64, // in meters
+    fuel_type: String,
+    operator: String,
+    created_at: u64,
+    #[serde(default)]
+    min_flight_crew: u64, // 0 derives it from the size class
+    #[serde(default)]
+    base_airstrip_id: Option<u64>,
+    #[serde(default)]
+    airframe_minutes: u64, // total time in service
+    #[serde(default)]
+    engine_minutes: u64, // since the engine was new or last overhauled
+    #[serde(default)]
+    landing_cycles: u64,
+    #[serde(default)]
+    grounded: Option<Grounding>,
+    #[serde(default)]
+    inspections: Vec<Inspection>,
+}
+
+impl Aircraft {
+    fn size_class(&self) -> SizeClass {
+        match self.mtow {
+            0..=5_700 => SizeClass::Light,
+            5_701..=27_000 => SizeClass::Medium,
+            _ => SizeClass::Heavy,
+        }
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+    // Pilots the aircraft must be flown with
+    fn flight_crew(&self) -> u64 {
+        match (self.min_flight_crew, self.size_class()) {
+            (0, SizeClass::Light) => 1,
+            (0, _) => 2,
+            (crew, _) => crew,
+        }
     }
-}
 
-impl BoundedStorable for PilotSchedule {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
-}
+    // Refuses an aircraft that is grounded or will be past an inspection limit at
+    // `at`, or that would fly past an airframe-hours limit in the next
+    // `flight_minutes`
+    fn check_airworthy(&self, at: u64, flight_minutes: u64) -> Result<(), Message> {
+        if let Some(grounding) = &self.grounded {
+            return Err(Message::Error(format!(
+                "Aircraft {} is grounded: {}",
+                self.registration, grounding.reason
+            )));
+        }
+        if let Some(inspection) = self.inspections.iter().find(|inspection| self.inspection_overdue(inspection, at)) {
+            return Err(Message::Error(format!(
+                "Aircraft {} is overdue for its {} inspection",
+                self.registration,
+                inspection.kind.label()
+            )));
+        }
+        let after_flight = self.airframe_minutes.saturating_add(flight_minutes);
+        match self.inspections.iter().find(|inspection| {
+            !inspection.closed && inspection.due_airframe_minutes.is_some_and(|due| after_flight > due)
+        }) {
+            Some(inspection) => Err(Message::Error(format!(
+                "Aircraft {} would pass its {} inspection limit on this flight",
+                self.registration,
+                inspection.kind.label()
+            ))),
+            None => Ok(()),
+        }
+    }
 
-// Implementing Storable for EmergencyProtocol
-impl Storable for EmergencyProtocol {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+    fn inspection_overdue(&self, inspection: &Inspection, at: u64) -> bool {
+        !inspection.closed
+            && (inspection.due_at.is_some_and(|due| due <= at)
+                || inspection.due_airframe_minutes.is_some_and(|due| self.airframe_minutes >= due)
+                || inspection.due_cycles.is_some_and(|due| self.landing_cycles >= due))
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+    fn inspection_status(&self, inspection: &Inspection, at: u64) -> InspectionStatus {
+        InspectionStatus {
+            inspection: inspection.clone(),
+            overdue: self.inspection_overdue(inspection, at),
+            remaining_minutes: inspection.due_airframe_minutes.map(|due| due.saturating_sub(self.airframe_minutes)),
+            remaining_days: inspection.due_at.map(|due| due.saturating_sub(at) / NANOS_PER_DAY),
+            remaining_cycles: inspection.due_cycles.map(|due| due.saturating_sub(self.landing_cycles)),
+        }
     }
 }
 
-impl BoundedStorable for EmergencyProtocol {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Grounding struct: why an aircraft was taken out of service
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Grounding {
+    reason: String,
+    since: u64,
 }
 
-// Implementing Storable for FuelInventory
-impl Storable for FuelInventory {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// InspectionKind enum
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+enum InspectionKind {
+    #[default]
+    HundredHour,
+    Annual,
+    AirworthinessDirective { reference: String },
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+impl InspectionKind {
+    fn label(&self) -> String {
+        match self {
+            InspectionKind::HundredHour => "100-hour".to_string(),
+            InspectionKind::Annual => "annual".to_string(),
+            InspectionKind::AirworthinessDirective { reference } => format!("AD {}", reference),
+        }
     }
 }
 
-impl BoundedStorable for FuelInventory {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+// Inspection struct: a check the aircraft must pass to stay airworthy. It falls due
+// at whichever of its calendar, flight-hour or landing-cycle limits comes first.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Inspection {
+    id: u64,
+    kind: InspectionKind,
+    interval_minutes: u64, // 0 when the inspection does not repeat by flight time
+    interval_days: u64,
+    interval_cycles: u64,
+    due_at: Option<u64>,
+    due_airframe_minutes: Option<u64>,
+    due_cycles: Option<u64>,
+    last_done_at: Option<u64>,
+    closed: bool, // one-off directives close once complied with
 }
 
-// Implementing Storable for Revenue
-impl Storable for Revenue {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
+impl Inspection {
+    fn repeats(&self) -> bool {
+        self.interval_minutes > 0 || self.interval_days > 0 || self.interval_cycles > 0
     }
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
+    // Sets the next limits from the moment and totals at which the inspection was done
+    fn reset(&mut self, done_at: u64, airframe_minutes: u64, landing_cycles: u64) {
+        let after = |interval: u64, from: u64| (interval > 0).then(|| from.saturating_add(interval));
+        self.last_done_at = Some(done_at);
+        self.due_at = after(self.interval_days.saturating_mul(NANOS_PER_DAY), done_at);
+        self.due_airframe_minutes = after(self.interval_minutes, airframe_minutes);
+        self.due_cycles = after(self.interval_cycles, landing_cycles);
+        self.closed = !self.repeats();
     }
 }
 
-impl BoundedStorable for Revenue {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AddInspectionPayload {
+    kind: InspectionKind,
+    interval_minutes: u64, // 100-hour inspections default to 6,000 minutes
+    interval_days: u64,    // annual inspections default to 365 days
+    interval_cycles: u64,
+    // First limits; each defaults to one interval from now
+    due_at: Option<u64>,
+    due_airframe_minutes: Option<u64>,
+    due_cycles: Option<u64>,
 }
 
-// Implementing Storable for MaintenanceSchedule
-impl Storable for MaintenanceSchedule {
-    fn to_bytes(&self) -> Cow<[u8]> {
-        Cow::Owned(Encode!(self).unwrap())
-    }
+// InspectionStatus struct: an inspection with what is left before it falls due
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct InspectionStatus {
+    inspection: Inspection,
+    overdue: bool,
+    remaining_minutes: Option<u64>,
+    remaining_days: Option<u64>,
+    remaining_cycles: Option<u64>,
+}
 
-    fn from_bytes(bytes: Cow<[u8]>) -> Self {
-        Decode!(bytes.as_ref(), Self).unwrap()
-    }
+// AircraftStatus struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct AircraftStatus {
+    aircraft: Aircraft,
+    airworthy: bool,
+    inspections: Vec<InspectionStatus>,
 }
 
-impl BoundedStorable for MaintenanceSchedule {
-    const MAX_SIZE: u32 = 512;
-    const IS_FIXED_SIZE: bool = false;
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AircraftDueFilter {
+    airstrip_id: u64, // aircraft based at this airstrip
+    as_of: Option<u64>,
+    within_days: u64,
+    within_flight_hours: u64,
+    within_cycles: u64,
 }
 
-// Memory management
-thread_local! {
-    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
-        MemoryManager::init(DefaultMemoryImpl::default())
-    );
+// Pilot struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Pilot {
+    id: u64,
+    name: String,
+    license_number: String,
+    experience_years: u64,
+    contact: String,
+    email: String,
+    #[serde(default)]
+    license_class: LicenseClass,
+    #[serde(default)]
+    license_expiry: Option<u64>, // None until the licence details are recorded
+    #[serde(default)]
+    medical_class: MedicalClass,
+    #[serde(default)]
+    medical_expiry: Option<u64>,
+    #[serde(default)]
+    type_ratings: Vec<String>, // aircraft types, matched against Aircraft::aircraft_type
+    #[serde(default)]
+    crew_roles: Vec<CrewRole>, // empty means flight-deck roles only
+}
 
-    static ID_COUNTER: RefCell<IdCell> = RefCell::new(
-        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0)
-            .expect("Cannot create a counter")
-    );
+impl Pilot {
+    fn can_fill(&self, role: CrewRole) -> bool {
+        if self.crew_roles.is_empty() {
+            role.is_flight_crew()
+        } else {
+            self.crew_roles.contains(&role)
+        }
+    }
 
-    static AIRSTRIPS: RefCell<StableBTreeMap<u64, Airstrip, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
-        ));
+    // Whether the pilot may fly the aircraft on a duty that ends at `until`
+    fn check_eligibility(&self, aircraft: &Aircraft, until: u64) -> Result<(), IneligibilityReason> {
+        match self.license_expiry {
+            None => return Err(IneligibilityReason::LicenseNotRecorded),
+            Some(expiry) if expiry < until => return Err(IneligibilityReason::LicenseExpired { expired_at: expiry }),
+            Some(_) => {}
+        }
+        if self.license_class < REQUIRED_LICENSE_CLASS {
+            return Err(IneligibilityReason::LicenseClassTooLow {
+                held: self.license_class,
+                required: REQUIRED_LICENSE_CLASS,
+            });
+        }
 
-    static FLIGHTS: RefCell<StableBTreeMap<u64, Flight, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11)))
-        ));
+        match self.medical_expiry {
+            None => return Err(IneligibilityReason::MedicalNotRecorded),
+            Some(expiry) if expiry < until => return Err(IneligibilityReason::MedicalExpired { expired_at: expiry }),
+            Some(_) => {}
+        }
+        if self.medical_class < REQUIRED_MEDICAL_CLASS {
+            return Err(IneligibilityReason::MedicalClassTooLow {
+                held: self.medical_class,
+                required: REQUIRED_MEDICAL_CLASS,
+            });
+        }
 
-    static PILOTS: RefCell<StableBTreeMap<u64, Pilot, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
-        ));
+        let rated = self
+            .type_ratings
+            .iter()
+            .any(|rating| rating.eq_ignore_ascii_case(&aircraft.aircraft_type));
+        if !rated {
+            return Err(IneligibilityReason::MissingTypeRating {
+                aircraft_type: aircraft.aircraft_type.clone(),
+            });
+        }
 
-    static MAINTENANCE_SCHEDULES: RefCell<StableBTreeMap<u64, MaintenanceSchedule, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))
-        ));
+        Ok(())
+    }
+}
 
-    static PILOT_SCHEDULES: RefCell<StableBTreeMap<u64, PilotSchedule, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
-        ));
+// LicenseClass enum, ordered from least to most privileged
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum LicenseClass {
+    #[default]
+    Student,
+    Private,
+    Commercial,
+    AirlineTransport,
+}
 
-    static EMERGENCY_PROTOCOLS: RefCell<StableBTreeMap<u64, EmergencyProtocol, Memory>> =
+// MedicalClass enum, ordered from the least to the most demanding standard
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum MedicalClass {
+    #[default]
+    Class3,
+    Class2,
+    Class1,
+}
+
+// Minimum licence and medical a pilot needs to be rostered on a scheduled flight
+const REQUIRED_LICENSE_CLASS: LicenseClass = LicenseClass::Commercial;
+const REQUIRED_MEDICAL_CLASS: MedicalClass = MedicalClass::Class1;
+
+// IneligibilityReason enum: why a pilot cannot be rostered on a flight
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Debug)]
+enum IneligibilityReason {
+    LicenseNotRecorded,
+    LicenseExpired { expired_at: u64 },
+    LicenseClassTooLow { held: LicenseClass, required: LicenseClass },
+    MedicalNotRecorded,
+    MedicalExpired { expired_at: u64 },
+    MedicalClassTooLow { held: MedicalClass, required: MedicalClass },
+    MissingTypeRating { aircraft_type: String },
+    RoleNotHeld { role: CrewRole },
+    NotRecent {
+        period_days: u64,
+        takeoffs: u64,
+        landings: u64,
+        required_takeoffs: u64,
+        required_landings: u64,
+    },
+    NotNightRecent {
+        period_days: u64,
+        night_landings: u64,
+        required_night_landings: u64,
+    },
+    Unavailable { block_id: u64, reason: UnavailabilityReason, until: u64 },
+    OutsideWeeklyAvailability,
+}
+
+// LogbookEntry struct: credited to a pilot when a rostered flight completes.
+// Day and night follow local solar time at the flight's airstrip.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LogbookEntry {
+    id: u64,
+    pilot_id: u64,
+    schedule_id: u64,
+    flight_id: u64,
+    airstrip_id: u64,
+    aircraft_id: u64,
+    aircraft_type: String,
+    registration: String,
+    departure_time: u64,
+    arrival_time: u64,
+    flight_minutes: u64,
+    night_minutes: u64,
+    night_takeoff: bool,
+    night_landing: bool,
+    created_at: u64,
+    // Only the pilot in command is credited the takeoff and landing
+    #[serde(default)]
+    role: CrewRole,
+}
+
+// LogbookFilter struct: the time window applies to the arrival time
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LogbookFilter {
+    aircraft_type: Option<String>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+}
+
+// TypeTotals struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct TypeTotals {
+    aircraft_type: String,
+    flights: u64,
+    flight_minutes: u64,
+}
+
+// LogbookTotals struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LogbookTotals {
+    pilot_id: u64,
+    flights: u64,
+    flight_minutes: u64,
+    night_minutes: u64,
+    day_takeoffs: u64,
+    night_takeoffs: u64,
+    day_landings: u64,
+    night_landings: u64,
+    by_aircraft_type: Vec<TypeTotals>,
+    last_flight_at: Option<u64>,
+}
+
+// Recency struct: takeoffs and landings logged as pilot in command within [since, until)
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Recency {
+    pilot_id: u64,
+    since: u64,
+    until: u64,
+    aircraft_type: Option<String>,
+    takeoffs: u64,
+    landings: u64,
+    night_takeoffs: u64,
+    night_landings: u64,
+}
+
+// RecencyPolicy struct: when enforced, schedule_pilot requires the minimums
+// within the period before the duty starts. Night landings are only required
+// for flights that depart or arrive at night.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct RecencyPolicy {
+    enforce: bool,
+    period_days: u64,
+    min_takeoffs: u64,
+    min_landings: u64,
+    min_night_landings: u64,
+    per_aircraft_type: bool,
+}
+
+impl Default for RecencyPolicy {
+    fn default() -> Self {
+        RecencyPolicy {
+            enforce: false,
+            period_days: 90,
+            min_takeoffs: 3,
+            min_landings: 3,
+            min_night_landings: 3,
+            per_aircraft_type: false,
+        }
+    }
+}
+
+const NANOS_PER_DAY: u64 = 24 * NANOS_PER_HOUR;
+// Night is 18:00 to 06:00 local solar time
+const NIGHT_START_HOUR: u64 = 18;
+const NIGHT_END_HOUR: u64 = 6;
+
+// Unavailability struct: a period in which the pilot cannot be rostered
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Unavailability {
+    id: u64,
+    pilot_id: u64,
+    start_time: u64,
+    end_time: u64,
+    reason: UnavailabilityReason,
+    note: String,
+    created_at: u64,
+}
+
+// UnavailabilityReason enum
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum UnavailabilityReason {
+    #[default]
+    Leave,
+    Sick,
+    Training,
+    Other,
+}
+
+// UnavailabilityPayload struct
+#[derive(candid::CandidType, Serialize, Deserialize, Default)]
+struct UnavailabilityPayload {
+    pilot_id: u64,
+    start_time: u64,
+    end_time: u64,
+    reason: UnavailabilityReason,
+    note: String,
+}
+
+// UnavailabilityRecorded struct: the new block and the pilot's schedules it clashes with
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct UnavailabilityRecorded {
+    block: Unavailability,
+    conflicts: Vec<PilotSchedule>,
+}
+
+// Longest single unavailability block; also bounds how far back index scans
+// must look for blocks that overlap a given time
+const MAX_UNAVAILABILITY_DAYS: u64 = 366;
+
+// Weekday enum
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum Weekday {
+    #[default]
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    // Weekday of a day counted from the Unix epoch, which fell on a Thursday
+    fn of_day(day: u64) -> Self {
+        match (day + 3) % 7 {
+            0 => Weekday::Monday,
+            1 => Weekday::Tuesday,
+            2 => Weekday::Wednesday,
+            3 => Weekday::Thursday,
+            4 => Weekday::Friday,
+            5 => Weekday::Saturday,
+            _ => Weekday::Sunday,
+        }
+    }
+}
+
+// AvailabilityWindow struct: minutes from local midnight, end exclusive
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AvailabilityWindow {
+    day: Weekday,
+    start_minute: u64,
+    end_minute: u64,
+}
+
+// WeeklyAvailability struct: a pilot's recurring availability. When windows are
+// set, duties must fall inside them, read in local solar time at the flight's airstrip.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WeeklyAvailability {
+    pilot_id: u64,
+    windows: Vec<AvailabilityWindow>,
+    updated_at: u64,
+}
+
+const MINUTES_PER_DAY: u64 = 24 * 60;
+
+// FtlWindow enum: the rolling periods flight time limitations are measured over
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+enum FtlWindow {
+    Hours24,
+    Days7,
+    Days28,
+    Days365,
+}
+
+impl FtlWindow {
+    const ALL: [FtlWindow; 4] = [FtlWindow::Hours24, FtlWindow::Days7, FtlWindow::Days28, FtlWindow::Days365];
+
+    fn length(self) -> u64 {
+        match self {
+            FtlWindow::Hours24 => 24 * NANOS_PER_HOUR,
+            FtlWindow::Days7 => 7 * 24 * NANOS_PER_HOUR,
+            FtlWindow::Days28 => 28 * 24 * NANOS_PER_HOUR,
+            FtlWindow::Days365 => 365 * 24 * NANOS_PER_HOUR,
+        }
+    }
+}
+
+// WindowLimits struct: minutes allowed in each rolling window; 0 means no limit
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WindowLimits {
+    per_24_hours: u64,
+    per_7_days: u64,
+    per_28_days: u64,
+    per_365_days: u64,
+}
+
+impl WindowLimits {
+    fn get(&self, window: FtlWindow) -> u64 {
+        match window {
+            FtlWindow::Hours24 => self.per_24_hours,
+            FtlWindow::Days7 => self.per_7_days,
+            FtlWindow::Days28 => self.per_28_days,
+            FtlWindow::Days365 => self.per_365_days,
+        }
+    }
+}
+
+// FtlLimits struct: duty counts the whole PilotSchedule window, flight time the
+// part of it between the flight's departure and arrival
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct FtlLimits {
+    duty_minutes: WindowLimits,
+    flight_minutes: WindowLimits,
+    min_rest_minutes: u64,
+}
+
+impl Default for FtlLimits {
+    fn default() -> Self {
+        FtlLimits {
+            duty_minutes: WindowLimits {
+                per_24_hours: 13 * 60,
+                per_7_days: 60 * 60,
+                per_28_days: 190 * 60,
+                per_365_days: 2000 * 60,
+            },
+            flight_minutes: WindowLimits {
+                per_24_hours: 8 * 60,
+                per_7_days: 35 * 60,
+                per_28_days: 100 * 60,
+                per_365_days: 900 * 60,
+            },
+            min_rest_minutes: 12 * 60,
+        }
+    }
+}
+
+// Longest single duty period schedule_pilot accepts
+const MAX_DUTY_LENGTH: u64 = 24 * NANOS_PER_HOUR;
+const MAX_MIN_REST_MINUTES: u64 = 7 * 24 * 60;
+
+// FtlViolation enum: the limit an assignment would break
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Debug)]
+enum FtlViolation {
+    DutyTime { window: FtlWindow, limit_minutes: u64, scheduled_minutes: u64 },
+    FlightTime { window: FtlWindow, limit_minutes: u64, scheduled_minutes: u64 },
+    InsufficientRest { required_minutes: u64, available_minutes: u64 },
+}
+
+// WindowUsage struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct WindowUsage {
+    window: FtlWindow,
+    limit_minutes: u64, // 0 means no limit
+    used_minutes: u64,
+    remaining_minutes: Option<u64>, // None when the window has no limit
+}
+
+// DutyAllowance struct: usage over the windows ending at `as_of`
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct DutyAllowance {
+    pilot_id: u64,
+    as_of: u64,
+    duty: Vec<WindowUsage>,
+    flight: Vec<WindowUsage>,
+    rested_at: Option<u64>, // earliest start for the next duty after the latest one before as_of
+}
+
+// PilotSchedule struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PilotSchedule {
+    id: u64,
+    pilot_id: u64,
+    flight_id: u64,
+    start_time: u64,
+    end_time: u64,
+    status: String, // "scheduled", "completed", "cancelled"
+    #[serde(default)]
+    role: CrewRole,
+}
+
+// CrewRole enum: the seat a crew member fills on a flight
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum CrewRole {
+    #[default]
+    PilotInCommand,
+    SecondInCommand,
+    CabinCrew,
+    FlightNurse,
+}
+
+impl CrewRole {
+    // Flight-deck roles need a valid licence, medical, type rating and recency, and earn logbook time
+    fn is_flight_crew(self) -> bool {
+        matches!(self, CrewRole::PilotInCommand | CrewRole::SecondInCommand)
+    }
+}
+
+// CrewComplement struct: how many crew a flight needs, or has, in each role
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default, PartialEq)]
+struct CrewComplement {
+    pilots_in_command: u64,
+    second_in_command: u64,
+    cabin_crew: u64,
+    flight_nurses: u64,
+}
+
+impl CrewComplement {
+    fn count(&self, role: CrewRole) -> u64 {
+        match role {
+            CrewRole::PilotInCommand => self.pilots_in_command,
+            CrewRole::SecondInCommand => self.second_in_command,
+            CrewRole::CabinCrew => self.cabin_crew,
+            CrewRole::FlightNurse => self.flight_nurses,
+        }
+    }
+
+    fn add(&mut self, role: CrewRole) {
+        match role {
+            CrewRole::PilotInCommand => self.pilots_in_command += 1,
+            CrewRole::SecondInCommand => self.second_in_command += 1,
+            CrewRole::CabinCrew => self.cabin_crew += 1,
+            CrewRole::FlightNurse => self.flight_nurses += 1,
+        }
+    }
+
+    // Roles in which `self` falls short of `required`, with how many are missing
+    fn shortfall(&self, required: &CrewComplement) -> Vec<CrewShortfall> {
+        [CrewRole::PilotInCommand, CrewRole::SecondInCommand, CrewRole::CabinCrew, CrewRole::FlightNurse]
+            .into_iter()
+            .filter(|role| self.count(*role) < required.count(*role))
+            .map(|role| CrewShortfall {
+                role,
+                missing: required.count(role) - self.count(role),
+            })
+            .collect()
+    }
+}
+
+// CrewShortfall struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct CrewShortfall {
+    role: CrewRole,
+    missing: u64,
+}
+
+// FlightCrew struct: required and rostered crew for one flight
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct FlightCrew {
+    flight_id: u64,
+    flight_number: String,
+    airstrip_id: u64,
+    departure_time: u64,
+    category: FlightCategory,
+    required: CrewComplement,
+    rostered: CrewComplement,
+    shortfall: Vec<CrewShortfall>,
+    schedules: Vec<PilotSchedule>,
+}
+
+// One cabin crew member per this many passenger seats, required from CABIN_CREW_MIN_SEATS seats up
+const SEATS_PER_CABIN_CREW: u64 = 50;
+const CABIN_CREW_MIN_SEATS: u64 = 20;
+
+// RosterRequest struct: the flights to crew and whether to save the result
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RosterRequest {
+    airstrip_id: Option<u64>, // None rosters every airstrip
+    from: u64,
+    to: u64,
+    commit: bool, // false only proposes the assignments
+    cursor: Option<String>, // the previous result's `resume_from`
+}
+
+// RosterResult struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct RosterResult {
+    committed: bool,
+    assignments: Vec<PilotSchedule>, // ids are 0 until committed
+    gaps: Vec<RosterGap>,
+    // Set when flights were left for another call: pass it as the next request's `cursor`
+    resume_from: Option<String>,
+}
+
+// RosterGap struct: a role the roster could not fill on a flight
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct RosterGap {
+    flight_id: u64,
+    flight_number: String,
+    role: CrewRole,
+    missing: u64,
+    reason: String,
+    rejections: Vec<CandidateRejection>, // each crew member who holds the role and why they could not take it
+}
+
+// CandidateRejection struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct CandidateRejection {
+    pilot_id: u64,
+    reason: Message,
+}
+
+// Auto-rostered duties run from report time before departure to release after arrival
+const DUTY_REPORT_MINUTES: u64 = 60;
+const DUTY_RELEASE_MINUTES: u64 = 30;
+const MAX_ROSTER_HORIZON_DAYS: u64 = 31;
+const MAX_ROSTER_FLIGHTS: usize = 40;
+
+// ArchiveCursor struct: the next record an unfinished archive_airstrip call would have taken
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize)]
+struct ArchiveCursor {
+    entity: EntityKind,
+    time: u64,
+    id: u64,
+}
+
+// AirstripArchive struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct AirstripArchive {
+    airstrip: Airstrip, // archived_at is set once every record has been archived
+    archived: u64,      // records this call archived
+    // Set when records remain: pass it as the next call's cursor
+    resume_from: Option<ArchiveCursor>,
+}
+
+// The kinds of record archived with their airstrip, in the order a call works through them
+const ARCHIVE_ORDER: [EntityKind; 6] = [
+    EntityKind::Flight,
+    EntityKind::MaintenanceSchedule,
+    EntityKind::FuelInventory,
+    EntityKind::Revenue,
+    EntityKind::Expense,
+    EntityKind::EmergencyProtocol,
+];
+const MAX_ARCHIVE_RECORDS: usize = 500;
+
+// EmergencyProtocol struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct EmergencyProtocol {
+    id: u64,
+    airstrip_id: u64,
+    protocol_type: ProtocolType,
+    description: String,
+    contact_numbers: Vec<String>,
+    evacuation_routes: Vec<String>,
+    created_at: u64,
+    archived_at: Option<u64>, // set when the protocol is retired or its airstrip archived
+    revision: u64,
+    approved_by: Option<Principal>, // unknown for protocols written before revisions were kept
+    approved_at: u64,
+}
+
+// ProtocolType enum
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+enum ProtocolType {
+    #[default]
+    Weather,
+    Technical,
+    Security,
+    Medical,
+    Fire,
+    Other { name: String },
+}
+
+// ProtocolRevision struct: one approved version of an emergency protocol
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ProtocolRevision {
+    id: u64,
+    protocol_id: u64,
+    revision: u64,
+    protocol_type: ProtocolType,
+    description: String,
+    contact_numbers: Vec<String>,
+    evacuation_routes: Vec<String>,
+    change_summary: String,
+    approved_by: Option<Principal>,
+    approved_at: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UpdateProtocolPayload {
+    protocol_type: Option<ProtocolType>,
+    description: Option<String>,
+    contact_numbers: Option<Vec<String>>,
+    evacuation_routes: Option<Vec<String>>,
+    change_summary: String, // required: what changed in this revision
+}
+
+// FuelInventory struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct FuelInventory {
+    id: u64,
+    airstrip_id: u64,
+    fuel_type: String,
+    quantity: f64,
+    unit_price: f64,
+    last_updated: u64,
+    archived_at: Option<u64>,
+}
+
+// Revenue struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Revenue {
+    id: u64,
+    airstrip_id: u64,
+    source: String, // "landing_fees", "fuel_sales", "parking", "maintenance"
+    amount: f64,
+    transaction_date: u64,
+    description: String,
+    archived_at: Option<u64>,
+}
+
+// OccupancySnapshot struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct OccupancySnapshot {
+    timestamp: u64,
+    stand_count: u64,
+    occupied: u64,
+    available: u64,
+    flight_ids: Vec<u64>,
+}
+
+// HourlySlotUsage struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct HourlySlotUsage {
+    slot_start: u64,
+    arrivals: u64,
+    departures: u64,
+    movements_per_hour: u64,
+    peak_occupancy: u64,
+    stand_count: u64,
+}
+
+// MaintenanceSchedule struct: a maintenance window and its work order
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct MaintenanceSchedule {
+    id: u64,
+    airstrip_id: u64,
+    date: u64,
+    description: String,
+    status: MaintenanceStatus,
+    archived_at: Option<u64>,
+    #[serde(default)]
+    end_time: u64, // the window runs from `date` to here; 0 on older records means the whole day
+    #[serde(default)]
+    impact: MaintenanceImpact,
+    #[serde(default)]
+    runway_id: Option<u64>, // None means every runway at the airstrip
+    #[serde(default)]
+    technicians: Vec<String>,
+    #[serde(default)]
+    tasks: Vec<WorkTask>,
+    #[serde(default)]
+    parts: Vec<PartUsage>,
+    #[serde(default)]
+    labour: Vec<LabourEntry>,
+    #[serde(default)]
+    completion_notes: String,
+    #[serde(default)]
+    started_at: Option<u64>,
+    #[serde(default)]
+    completed_at: Option<u64>,
+    #[serde(default)]
+    signed_off_by: Option<Principal>,
+    #[serde(default)]
+    expense_id: Option<u64>, // the expense booked for the work order's cost on completion
+    #[serde(default)]
+    plan_id: Option<u64>, // the maintenance plan that generated it
+}
+
+impl MaintenanceSchedule {
+    fn window(&self) -> (u64, u64) {
+        if self.end_time > self.date {
+            (self.date, self.end_time)
+        } else {
+            (self.date, self.date.saturating_add(NANOS_PER_DAY))
+        }
+    }
+
+    fn is_live(&self) -> bool {
+        self.archived_at.is_none() && matches!(self.status, MaintenanceStatus::Scheduled | MaintenanceStatus::InProgress)
+    }
+
+    fn parts_cost(&self) -> f64 {
+        self.parts.iter().map(|part| part.quantity as f64 * part.unit_cost).sum()
+    }
+
+    fn labour_cost(&self) -> f64 {
+        self.labour.iter().map(|entry| entry.hours * entry.hourly_rate).sum()
+    }
+}
+
+// MaintenanceStatus enum
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, Debug)]
+enum MaintenanceStatus {
+    #[default]
+    Scheduled,
+    InProgress,
+    Completed,
+    Cancelled,
+}
+
+impl MaintenanceStatus {
+    fn can_transition_to(self, next: MaintenanceStatus) -> bool {
+        use MaintenanceStatus::*;
+        matches!(
+            (self, next),
+            (Scheduled, InProgress | Cancelled) | (InProgress, Completed | Cancelled)
+        )
+    }
+}
+
+// WorkTask struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct WorkTask {
+    description: String,
+    done: bool,
+}
+
+// PartUsage struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PartUsage {
+    part_number: String,
+    description: String,
+    quantity: u64,
+    unit_cost: f64,
+}
+
+// LabourEntry struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct LabourEntry {
+    technician: String,
+    hours: f64,
+    hourly_rate: f64,
+}
+
+// MaintenancePlan struct: preventive maintenance that recurs. Instances are
+// generated ahead of time as MaintenanceSchedule records.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct MaintenancePlan {
+    id: u64,
+    airstrip_id: u64,
+    name: String,
+    description: String,
+    recurrence: Recurrence,
+    starts_at: u64, // first occurrence; monthly plans also take their time of day from it
+    duration_minutes: u64,
+    impact: MaintenanceImpact,
+    runway_id: Option<u64>,
+    tasks: Vec<String>,
+    last_due: Option<u64>, // due time of the latest generated instance
+    active: bool,
+    created_at: u64,
+}
+
+// Recurrence enum
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, PartialEq, Debug)]
+enum Recurrence {
+    EveryDays { days: u64 },
+    MonthlyOnDay { day: u32 }, // falls on the month's last day in shorter months
+    AfterMovements { movements: u64 }, // counts scheduled as well as flown movements at the airstrip
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct CreateMaintenancePlanPayload {
+    airstrip_id: u64,
+    name: String,
+    description: String,
+    recurrence: Recurrence,
+    starts_at: u64,
+    duration_minutes: u64,
+    impact: MaintenanceImpact,
+    runway_id: Option<u64>,
+    tasks: Vec<String>,
+}
+
+// MaintenanceDue struct: open work at an airstrip as of a point in time
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct MaintenanceDue {
+    airstrip_id: u64,
+    as_of: u64,
+    overdue: Vec<MaintenanceSchedule>, // should have started, or finished, by now
+    upcoming: Vec<MaintenanceSchedule>,
+    plans: Vec<PlanDue>,
+}
+
+// PlanDue struct: the next occurrence of a plan that has not been generated yet
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct PlanDue {
+    plan_id: u64,
+    name: String,
+    next_due: u64,
+}
+
+const MAX_PLAN_HORIZON_DAYS: u64 = 366;
+// Instances one plan may generate in a single call
+const MAX_INSTANCES_PER_PLAN: usize = 100;
+
+// Expense struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct Expense {
+    id: u64,
+    airstrip_id: u64,
+    category: String, // "maintenance", "fuel", "staff", "other"
+    amount: f64,
+    transaction_date: u64,
+    description: String,
+    maintenance_id: Option<u64>,
+    archived_at: Option<u64>,
+}
+
+// MaintenanceImpact enum: what the work takes out of service while it runs
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default, PartialEq, Debug)]
+enum MaintenanceImpact {
+    #[default]
+    NoImpact,
+    FullClosure,
+    ReducedLength { available_length: u64 }, // in meters
+    PartialStands { stand_ids: Vec<u64> },
+}
+
+// MaintenanceConflict struct: how a maintenance window bears on one flight.
+// Blocking conflicts stop a flight from being scheduled; the rest are warnings.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct MaintenanceConflict {
+    maintenance_id: u64,
+    flight_id: u64,
+    flight_number: String,
+    blocking: bool,
+    detail: String,
+}
+
+// FlightScheduled struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct FlightScheduled {
+    flight: Flight,
+    warnings: Vec<MaintenanceConflict>,
+}
+
+// MaintenanceScheduled struct: the maintenance and the existing flights it affects
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct MaintenanceScheduled {
+    maintenance: MaintenanceSchedule,
+    affected_flights: Vec<MaintenanceConflict>,
+}
+
+// Longest maintenance window; also bounds how far back index scans must look
+// for windows that overlap a given time
+const MAX_MAINTENANCE_WINDOW_DAYS: u64 = 90;
+
+// Payload structs
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CreateAirstripPayload {
+    name: String,
+    location: String,
+    coordinates: Option<Coordinates>,
+    contact: String,
+    email: String,
+    runway_length: u64,
+    capacity: u64,
+    movements_per_hour: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UpdateAirstripPayload {
+    name: Option<String>,
+    location: Option<String>,
+    coordinates: Option<Coordinates>,
+    contact: Option<String>,
+    email: Option<String>,
+    runway_length: Option<u64>,
+    capacity: Option<u64>,
+    movements_per_hour: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ScheduleFlightPayload {
+    airstrip_id: u64,
+    flight_number: String,
+    destination: String,
+    departure_time: u64,
+    arrival_time: u64,
+    aircraft_id: u64,
+    runway_id: Option<u64>,
+    movement: MovementType,
+    ground_time_minutes: u64,
+    category: FlightCategory,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RescheduleFlightPayload {
+    departure_time: u64,
+    arrival_time: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AddStandPayload {
+    airstrip_id: u64,
+    name: String,
+    size_class: SizeClass,
+    has_fuel: bool,
+    has_power: bool,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UpdateStandPayload {
+    name: Option<String>,
+    size_class: Option<SizeClass>,
+    has_fuel: Option<bool>,
+    has_power: Option<bool>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RegisterAircraftPayload {
+    registration: String,
+    aircraft_type: String,
+    mtow: u64,
+    seat_count: u64,
+    takeoff_distance_required: u64,
+    landing_distance_required: u64,
+    fuel_type: String,
+    operator: String,
+    min_flight_crew: u64,
+    base_airstrip_id: Option<u64>,
+    airframe_minutes: u64,
+    engine_minutes: u64,
+    landing_cycles: u64,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UpdateAircraftPayload {
+    aircraft_type: Option<String>,
+    mtow: Option<u64>,
+    seat_count: Option<u64>,
+    takeoff_distance_required: Option<u64>,
+    landing_distance_required: Option<u64>,
+    fuel_type: Option<String>,
+    operator: Option<String>,
+    min_flight_crew: Option<u64>,
+    base_airstrip_id: Option<u64>,
+    // Corrections, and resetting engine time after an overhaul
+    airframe_minutes: Option<u64>,
+    engine_minutes: Option<u64>,
+    landing_cycles: Option<u64>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AddRunwayPayload {
+    airstrip_id: u64,
+    designator: String,
+    heading: u16,
+    surface: RunwaySurface,
+    width: u64,
+    lighting: RunwayLighting,
+    declared_distances: DeclaredDistances,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UpdateRunwayPayload {
+    designator: Option<String>,
+    heading: Option<u16>,
+    surface: Option<RunwaySurface>,
+    width: Option<u64>,
+    lighting: Option<RunwayLighting>,
+    declared_distances: Option<DeclaredDistances>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct RegisterPilotPayload {
+    name: String,
+    license_number: String,
+    experience_years: u64,
+    contact: String,
+    email: String,
+    license_class: LicenseClass,
+    license_expiry: u64,
+    medical_class: MedicalClass,
+    medical_expiry: u64,
+    type_ratings: Vec<String>,
+    crew_roles: Vec<CrewRole>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UpdatePilotPayload {
+    name: Option<String>,
+    license_number: Option<String>,
+    experience_years: Option<u64>,
+    contact: Option<String>,
+    email: Option<String>,
+    license_class: Option<LicenseClass>,
+    license_expiry: Option<u64>,
+    medical_class: Option<MedicalClass>,
+    medical_expiry: Option<u64>,
+    type_ratings: Option<Vec<String>>,
+    crew_roles: Option<Vec<CrewRole>>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ScheduleMaintenancePayload {
+    airstrip_id: u64,
+    date: u64,
+    description: String,
+    end_time: u64,
+    impact: MaintenanceImpact,
+    runway_id: Option<u64>,
+    technicians: Vec<String>,
+    tasks: Vec<String>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct UpdateWorkOrderPayload {
+    technicians: Option<Vec<String>>,
+    tasks: Option<Vec<WorkTask>>,
+    parts: Option<Vec<PartUsage>>,
+    labour: Option<Vec<LabourEntry>>,
+}
+
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Debug)]
+enum Message {
+    Success(String),
+    Error(String),
+    NotFound(String),
+    InvalidPayload(String),
+    Unauthorized(String),
+    PilotIneligible(IneligibilityReason),
+    FtlExceeded(FtlViolation),
+}
+
+// Role enum
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+enum Role {
+    SystemAdmin,
+    AirstripManager(u64), // scoped to a single airstrip
+    Dispatcher,
+    Finance,
+    Pilot(u64), // linked to a pilot record
+}
+
+impl Role {
+    fn manages(&self, airstrip_id: u64) -> bool {
+        matches!(self, Role::AirstripManager(id) if *id == airstrip_id)
+    }
+}
+
+// UserRoles struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct UserRoles {
+    principal: Principal,
+    roles: Vec<Role>,
+    updated_at: u64,
+}
+
+// AuditEntry struct: one append-only record per mutation
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct AuditEntry {
+    id: u64,
+    caller: Principal,
+    timestamp: u64,
+    action: AuditAction,
+    entity: EntityKind,
+    entity_id: u64,
+    before: Option<String>, // JSON snapshot
+    after: Option<String>,  // JSON snapshot
+}
+
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+enum AuditAction {
+    Create,
+    Update,
+    Delete,
+    Archive,
+}
+
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Debug)]
+enum EntityKind {
+    Airstrip,
+    Runway,
+    Stand,
+    Flight,
+    Aircraft,
+    Pilot,
+    PilotSchedule,
+    MaintenanceSchedule,
+    FuelInventory,
+    Revenue,
+    EmergencyProtocol,
+    LogbookEntry,
+    Unavailability,
+    WeeklyAvailability,
+    SlotReservation,
+    StandAssignment,
+    Expense,
+    MaintenancePlan,
+    RecencyPolicy,
+    FtlLimits,
+}
+
+// CascadeReport struct: every record a cancellation or move changed
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct CascadeReport {
+    affected: Vec<AffectedRecord>,
+    warnings: Vec<MaintenanceConflict>,
+}
+
+// AffectedRecord struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct AffectedRecord {
+    entity: EntityKind,
+    id: u64,
+    effect: CascadeEffect,
+}
+
+// CascadeEffect enum
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+enum CascadeEffect {
+    Cancelled,
+    Released, // a slot or stand the record no longer holds
+    Created,  // a replacement slot or stand assignment
+    Moved { start_time: u64, end_time: u64 },
+}
+
+// AuditQuery struct: all filters are optional; cursor is the id to continue below
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct AuditQuery {
+    entity: Option<EntityKind>,
+    entity_id: Option<u64>,
+    caller: Option<Principal>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    cursor: Option<u64>,
+    limit: Option<u32>,
+}
+
+// AuditPage struct: newest entries first
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct AuditPage {
+    entries: Vec<AuditEntry>,
+    next_cursor: Option<u64>,
+}
+
+const DEFAULT_AUDIT_PAGE_SIZE: u32 = 50;
+const MAX_AUDIT_PAGE_SIZE: u32 = 200;
+
+// SortOrder enum
+#[derive(candid::CandidType, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+// PageRequest struct: shared by every list query. The cursor is opaque and
+// comes from the previous page's next_cursor; it only makes sense with the
+// same filter and order it was issued for.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PageRequest {
+    limit: Option<u32>,
+    cursor: Option<String>,
+    order: SortOrder,
+}
+
+// Page struct: next_cursor is None once there is nothing more to fetch
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    next_cursor: Option<String>,
+}
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 200;
+
+// FlightFilter struct: the time window applies to the movement time. Flights
+// come in movement-time order when airstrip_id is set, otherwise in creation order.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct FlightFilter {
+    airstrip_id: Option<u64>,
+    status: Option<FlightStatus>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    include_archived: bool,
+}
+
+// PilotFilter struct: name matches case-insensitively anywhere in the pilot's name
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PilotFilter {
+    name: Option<String>,
+    min_experience_years: Option<u64>,
+}
+
+// PilotScheduleFilter struct: the time window applies to the schedule's start time
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct PilotScheduleFilter {
+    status: Option<String>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+}
+
+// MaintenanceFilter struct: the time window applies to the maintenance date.
+// Schedules come in date order when airstrip_id is set, otherwise in creation order.
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct MaintenanceFilter {
+    airstrip_id: Option<u64>,
+    status: Option<MaintenanceStatus>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    include_archived: bool,
+}
+
+// ProtocolFilter struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct ProtocolFilter {
+    protocol_type: Option<ProtocolType>,
+    include_archived: bool,
+}
+
+// FuelFilter struct: the time window applies to when the record was last updated
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct FuelFilter {
+    fuel_type: Option<String>,
+    start_time: Option<u64>,
+    end_time: Option<u64>,
+    include_archived: bool,
+}
+
+// Principal wrapper so principals can key a StableBTreeMap
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct PrincipalKey(Principal);
+
+// Key of the secondary indexes: entries sort by owner (an airstrip, pilot or
+// stand), then by time, then by record id
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct IndexKey {
+    owner: u64,
+    time: u64,
+    id: u64,
+}
+
+impl IndexKey {
+    fn new(owner: u64, time: u64, id: u64) -> Self {
+        IndexKey { owner, time, id }
+    }
+
+    // Every key of `owner` with a time in [start, end]
+    fn span(owner: u64, start: u64, end: u64) -> RangeInclusive<IndexKey> {
+        IndexKey::new(owner, start, 0)..=IndexKey::new(owner, end, u64::MAX)
+    }
+}
+
+// Schema versioning
+//
+// Every stored record is wrapped in an envelope: RECORD_MAGIC, the schema
+// version it was written under, then the payload. Since version 2 the payload
+// is MessagePack with struct fields stored by position, which is far smaller
+// than self-describing Candid; versions 1 and earlier hold Candid. Records
+// written before the envelope existed are bare Candid ("DIDL...") and count
+// as version 0.
+//
+// New fields go at the end of a struct with `#[serde(default)]` so MessagePack
+// records written before them still decode; that needs no version bump. Candid
+// ignores `#[serde(default)]` and only lets `Option` fields go missing, so a
+// type that still has Candid-era records (versions 0 and 1) keeps its Candid
+// layout below as a legacy struct, mapped in `decode_legacy`, as soon as it
+// gains any other field. Any other layout change bumps SCHEMA_VERSION, keeps
+// the old layout below as a legacy struct, and registers a migration in
+// MIGRATIONS.
+const SCHEMA_VERSION: u8 = 8;
+const RECORD_MAGIC: u8 = 0xA5;
+const FIRST_COMPACT_VERSION: u8 = 2;
+
+trait VersionedRecord: candid::CandidType + serde::de::DeserializeOwned {
+    // Decodes a payload written under an older schema version. The default
+    // covers MessagePack layouts that have only gained `#[serde(default)]`
+    // fields and Candid layouts that have only gained `Option` fields.
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        decode_payload(version, payload)
+    }
+}
+
+fn encode_record<T: candid::CandidType + serde::Serialize>(value: &T) -> Vec<u8> {
+    let mut bytes = vec![RECORD_MAGIC, SCHEMA_VERSION];
+    rmp_serde::encode::write(&mut bytes, value).expect("Failed to encode record");
+    bytes
+}
+
+fn decode_record<T: VersionedRecord>(bytes: &[u8]) -> T {
+    match bytes {
+        [RECORD_MAGIC, version, payload @ ..] if *version == SCHEMA_VERSION => decode_payload(*version, payload),
+        [RECORD_MAGIC, version, payload @ ..] if *version < SCHEMA_VERSION => T::decode_legacy(*version, payload),
+        [RECORD_MAGIC, version, ..] => ic_cdk::trap(&format!(
+            "Record was written by schema version {} but this build only knows up to {}",
+            version, SCHEMA_VERSION
+        )),
+        legacy => T::decode_legacy(0, legacy),
+    }
+}
+
+// Decodes a payload in the encoding used by the given schema version, into the current layout
+fn decode_payload<T: candid::CandidType + serde::de::DeserializeOwned>(version: u8, payload: &[u8]) -> T {
+    if version >= FIRST_COMPACT_VERSION {
+        rmp_serde::from_slice(payload).expect("Failed to decode record")
+    } else {
+        Decode!(payload, T).unwrap()
+    }
+}
+
+// Airstrip layout before coordinates and slot limits (schema version 0)
+#[derive(candid::CandidType, Deserialize)]
+struct AirstripV0 {
+    id: u64,
+    name: String,
+    location: String,
+    contact: String,
+    email: String,
+    runway_length: u64,
+    capacity: u64,
+    created_at: u64,
+}
+
+// Flight layout with a free-form status and no aircraft (schema version 0)
+#[derive(candid::CandidType, Deserialize)]
+struct FlightV0 {
+    id: u64,
+    airstrip_id: u64,
+    flight_number: String,
+    destination: String,
+    departure_time: u64,
+    arrival_time: u64,
+    status: String,
+}
+
+// Flight layout before flight categories (schema version 1)
+#[derive(candid::CandidType, Deserialize)]
+struct FlightV1 {
+    id: u64,
+    airstrip_id: u64,
+    flight_number: String,
+    destination: String,
+    departure_time: u64,
+    arrival_time: u64,
+    aircraft_id: u64,
+    runway_id: Option<u64>,
+    movement: MovementType,
+    ground_time_minutes: u64,
+    slot_id: Option<u64>,
+    stand_assignment_id: Option<u64>,
+    status: FlightStatus,
+    status_history: Vec<FlightStatusChange>,
+    archived_at: Option<u64>,
+}
+
+// Aircraft layout before crew minimums, hours, cycles and inspections (schema version 1)
+#[derive(candid::CandidType, Deserialize)]
+struct AircraftV1 {
+    id: u64,
+    registration: String,
+    aircraft_type: String,
+    mtow: u64,
+    seat_count: u64,
+    takeoff_distance_required: u64,
+    landing_distance_required: u64,
+    fuel_type: String,
+    operator: String,
+    created_at: u64,
+}
+
+// Pilot schedule layout before crew roles (schema versions 0 and 1)
+#[derive(candid::CandidType, Deserialize)]
+struct PilotScheduleV0 {
+    id: u64,
+    pilot_id: u64,
+    flight_id: u64,
+    start_time: u64,
+    end_time: u64,
+    status: String,
+}
+
+impl VersionedRecord for Airstrip {
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        if version > 0 {
+            return decode_payload(version, payload);
+        }
+        let old = Decode!(payload, AirstripV0).unwrap();
+        Airstrip {
+            id: old.id,
+            name: old.name,
+            location: old.location,
+            coordinates: None,
+            contact: old.contact,
+            email: old.email,
+            runway_length: old.runway_length,
+            capacity: old.capacity,
+            movements_per_hour: 0,
+            created_at: old.created_at,
+            archived_at: None,
+        }
+    }
+}
+
+// Maintenance layout as Candid stored it (schema versions 0 and 1)
+#[derive(candid::CandidType, Deserialize)]
+struct MaintenanceScheduleV0 {
+    id: u64,
+    airstrip_id: u64,
+    date: u64,
+    description: String,
+    status: String,
+    archived_at: Option<u64>,
+}
+
+// Compact maintenance layout with a free-form status (schema versions 2 to 4)
+#[derive(candid::CandidType, Deserialize)]
+struct MaintenanceScheduleV4 {
+    id: u64,
+    airstrip_id: u64,
+    date: u64,
+    description: String,
+    status: String,
+    #[serde(default)]
+    archived_at: Option<u64>,
+    #[serde(default)]
+    end_time: u64,
+    #[serde(default)]
+    impact: MaintenanceImpact,
+    #[serde(default)]
+    runway_id: Option<u64>,
+}
+
+// Emergency protocol layout with a free-form type and no revisions (schema versions 0 to 5)
+#[derive(candid::CandidType, Deserialize)]
+struct EmergencyProtocolV5 {
+    id: u64,
+    airstrip_id: u64,
+    protocol_type: String,
+    description: String,
+    contact_numbers: Vec<String>,
+    evacuation_routes: Vec<String>,
+    created_at: u64,
+    #[serde(default)]
+    archived_at: Option<u64>,
+}
+
+impl VersionedRecord for EmergencyProtocol {
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        let old: EmergencyProtocolV5 = decode_payload(version, payload);
+        let protocol_type = match old.protocol_type.trim().to_lowercase().as_str() {
+            "weather" => ProtocolType::Weather,
+            "technical" => ProtocolType::Technical,
+            "security" => ProtocolType::Security,
+            "medical" => ProtocolType::Medical,
+            "fire" => ProtocolType::Fire,
+            _ => ProtocolType::Other { name: old.protocol_type },
+        };
+        EmergencyProtocol {
+            id: old.id,
+            airstrip_id: old.airstrip_id,
+            protocol_type,
+            description: old.description,
+            contact_numbers: old.contact_numbers,
+            evacuation_routes: old.evacuation_routes,
+            created_at: old.created_at,
+            archived_at: old.archived_at,
+            revision: 1,
+            approved_by: None,
+            approved_at: old.created_at,
+        }
+    }
+}
+
+impl MaintenanceStatus {
+    // Reads the free-form status maintenance was stored with before schema version 5
+    fn from_legacy(status: &str) -> Self {
+        match status {
+            "completed" => MaintenanceStatus::Completed,
+            "cancelled" => MaintenanceStatus::Cancelled,
+            _ => MaintenanceStatus::Scheduled,
+        }
+    }
+}
+
+impl VersionedRecord for MaintenanceSchedule {
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        if version > 4 {
+            return decode_payload(version, payload);
+        }
+        if version < FIRST_COMPACT_VERSION {
+            let old = Decode!(payload, MaintenanceScheduleV0).unwrap();
+            return MaintenanceSchedule {
+                id: old.id,
+                airstrip_id: old.airstrip_id,
+                date: old.date,
+                description: old.description,
+                status: MaintenanceStatus::from_legacy(&old.status),
+                archived_at: old.archived_at,
+                ..Default::default()
+            };
+        }
+        let old: MaintenanceScheduleV4 = decode_payload(version, payload);
+        MaintenanceSchedule {
+            id: old.id,
+            airstrip_id: old.airstrip_id,
+            date: old.date,
+            description: old.description,
+            status: MaintenanceStatus::from_legacy(&old.status),
+            archived_at: old.archived_at,
+            end_time: old.end_time,
+            impact: old.impact,
+            runway_id: old.runway_id,
+            ..Default::default()
+        }
+    }
+}
+
+impl VersionedRecord for Flight {
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        if version >= FIRST_COMPACT_VERSION {
+            return decode_payload(version, payload);
+        }
+        if version == 1 {
+            let old = Decode!(payload, FlightV1).unwrap();
+            return Flight {
+                id: old.id,
+                airstrip_id: old.airstrip_id,
+                flight_number: old.flight_number,
+                destination: old.destination,
+                departure_time: old.departure_time,
+                arrival_time: old.arrival_time,
+                aircraft_id: old.aircraft_id,
+                runway_id: old.runway_id,
+                movement: old.movement,
+                ground_time_minutes: old.ground_time_minutes,
+                slot_id: old.slot_id,
+                stand_assignment_id: old.stand_assignment_id,
+                status: old.status,
+                status_history: old.status_history,
+                archived_at: old.archived_at,
+                category: FlightCategory::Passenger,
+            };
+        }
+        let old = Decode!(payload, FlightV0).unwrap();
+        let status = match old.status.as_str() {
+            "delayed" => FlightStatus::Delayed,
+            "arrived" => FlightStatus::Arrived,
+            "completed" => FlightStatus::Completed,
+            "cancelled" => FlightStatus::Cancelled,
+            _ => FlightStatus::Scheduled,
+        };
+        Flight {
+            id: old.id,
+            airstrip_id: old.airstrip_id,
+            flight_number: old.flight_number,
+            destination: old.destination,
+            departure_time: old.departure_time,
+            arrival_time: old.arrival_time,
+            aircraft_id: 0, // flown before the aircraft registry existed
+            runway_id: None,
+            movement: MovementType::Departure,
+            ground_time_minutes: 0,
+            slot_id: None,
+            stand_assignment_id: None,
+            status,
+            status_history: Vec::new(),
+            archived_at: None,
+            category: FlightCategory::Passenger,
+        }
+    }
+}
+
+// Pilot layout before licence, medical and crew role details (schema versions 0 and 1)
+#[derive(candid::CandidType, Deserialize)]
+struct PilotV0 {
+    id: u64,
+    name: String,
+    license_number: String,
+    experience_years: u64,
+    contact: String,
+    email: String,
+}
+
+impl VersionedRecord for Pilot {
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        if version >= FIRST_COMPACT_VERSION {
+            return decode_payload(version, payload);
+        }
+        let old = Decode!(payload, PilotV0).unwrap();
+        Pilot {
+            id: old.id,
+            name: old.name,
+            license_number: old.license_number,
+            experience_years: old.experience_years,
+            contact: old.contact,
+            email: old.email,
+            ..Default::default()
+        }
+    }
+}
+
+impl VersionedRecord for Aircraft {
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        if version >= FIRST_COMPACT_VERSION {
+            return decode_payload(version, payload);
+        }
+        let old = Decode!(payload, AircraftV1).unwrap();
+        Aircraft {
+            id: old.id,
+            registration: old.registration,
+            aircraft_type: old.aircraft_type,
+            mtow: old.mtow,
+            seat_count: old.seat_count,
+            takeoff_distance_required: old.takeoff_distance_required,
+            landing_distance_required: old.landing_distance_required,
+            fuel_type: old.fuel_type,
+            operator: old.operator,
+            created_at: old.created_at,
+            ..Default::default()
+        }
+    }
+}
+
+impl VersionedRecord for PilotSchedule {
+    fn decode_legacy(version: u8, payload: &[u8]) -> Self {
+        if version >= FIRST_COMPACT_VERSION {
+            return decode_payload(version, payload);
+        }
+        let old = Decode!(payload, PilotScheduleV0).unwrap();
+        PilotSchedule {
+            id: old.id,
+            pilot_id: old.pilot_id,
+            flight_id: old.flight_id,
+            start_time: old.start_time,
+            end_time: old.end_time,
+            status: old.status,
+            role: CrewRole::PilotInCommand, // every schedule was a flight-deck seat
+        }
+    }
+}
+
+impl VersionedRecord for AuditEntry {}
+impl VersionedRecord for UserRoles {}
+impl VersionedRecord for Runway {}
+impl VersionedRecord for SlotReservation {}
+impl VersionedRecord for Stand {}
+impl VersionedRecord for StandAssignment {}
+impl VersionedRecord for FuelInventory {}
+impl VersionedRecord for Revenue {}
+impl VersionedRecord for SchemaState {}
+impl VersionedRecord for FtlLimits {}
+impl VersionedRecord for LogbookEntry {}
+impl VersionedRecord for RecencyPolicy {}
+impl VersionedRecord for Unavailability {}
+impl VersionedRecord for WeeklyAvailability {}
+impl VersionedRecord for Expense {}
+impl VersionedRecord for MaintenancePlan {}
+impl VersionedRecord for ProtocolRevision {}
+
+// SchemaState struct: the version the stored data is at, plus the last migration run
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct SchemaState {
+    version: u8,
+    last_migration: Option<MigrationReport>,
+}
+
+// MigrationReport struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize, Default)]
+struct MigrationReport {
+    from_version: u8,
+    to_version: u8,
+    steps: Vec<String>,
+    records_migrated: u64,
+    started_at: u64,
+    completed_at: u64,
+}
+
+// SchemaInfo struct
+#[derive(candid::CandidType, Clone, Serialize, Deserialize)]
+struct SchemaInfo {
+    code_version: u8,
+    stored_version: u8,
+    pending_migrations: Vec<String>,
+    last_migration: Option<MigrationReport>,
+}
+
+// Migration struct: brings stored data up to `to_version`, returning how many records it rewrote
+struct Migration {
+    to_version: u8,
+    description: &'static str,
+    run: fn() -> u64,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        to_version: 1,
+        description: "Wrap every record in the versioned envelope",
+        run: rewrite_all_records,
+    },
+    Migration {
+        to_version: 2,
+        description: "Re-encode every record in the compact format",
+        run: rewrite_all_records,
+    },
+    Migration {
+        to_version: 3,
+        description: "Build the per-airstrip, per-pilot and per-stand indexes",
+        run: rebuild_indexes,
+    },
+    Migration {
+        to_version: 4,
+        description: "Index pilot schedules by flight",
+        run: index_schedules_by_flight,
+    },
+    Migration {
+        to_version: 5,
+        description: "Store maintenance status as an enum",
+        run: rewrite_maintenance_schedules,
+    },
+    Migration {
+        to_version: 6,
+        description: "Type emergency protocols and start their revision history",
+        run: migrate_emergency_protocols,
+    },
+    Migration {
+        to_version: 7,
+        description: "Index flights by aircraft",
+        run: index_flights_by_aircraft,
+    },
+    Migration {
+        to_version: 8,
+        description: "Index runways, stands and based aircraft by airstrip and flights by runway",
+        run: index_airstrip_resources,
+    },
+];
+
+// Implementing Storable for FtlLimits
+impl Storable for FtlLimits {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for LogbookEntry
+impl Storable for LogbookEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for RecencyPolicy
+impl Storable for RecencyPolicy {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Unavailability
+impl Storable for Unavailability {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for WeeklyAvailability
+impl Storable for WeeklyAvailability {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for SchemaState
+impl Storable for SchemaState {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for IndexKey
+impl Storable for IndexKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        let mut bytes = Vec::with_capacity(24);
+        bytes.extend_from_slice(&self.owner.to_be_bytes());
+        bytes.extend_from_slice(&self.time.to_be_bytes());
+        bytes.extend_from_slice(&self.id.to_be_bytes());
+        Cow::Owned(bytes)
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let part = |i: usize| u64::from_be_bytes(bytes[i * 8..(i + 1) * 8].try_into().unwrap());
+        IndexKey::new(part(0), part(1), part(2))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 24,
+        is_fixed_size: true,
+    };
+}
+
+// Implementing Storable for PrincipalKey
+impl Storable for PrincipalKey {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(self.0.as_slice().to_vec())
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        PrincipalKey(Principal::from_slice(bytes.as_ref()))
+    }
+
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 29,
+        is_fixed_size: false,
+    };
+}
+
+// Implementing Storable for AuditEntry (log entries are not size-bounded)
+impl Storable for AuditEntry {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for UserRoles
+impl Storable for UserRoles {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Airstrip
+impl Storable for Airstrip {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Runway
+impl Storable for Runway {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for SlotReservation
+impl Storable for SlotReservation {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Stand
+impl Storable for Stand {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for StandAssignment
+impl Storable for StandAssignment {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Flight
+impl Storable for Flight {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Aircraft
+impl Storable for Aircraft {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Pilot
+impl Storable for Pilot {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for PilotSchedule
+impl Storable for PilotSchedule {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for EmergencyProtocol
+impl Storable for EmergencyProtocol {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for FuelInventory
+impl Storable for FuelInventory {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Revenue
+impl Storable for Revenue {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for MaintenancePlan
+impl Storable for MaintenancePlan {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for ProtocolRevision
+impl Storable for ProtocolRevision {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for Expense
+impl Storable for Expense {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Implementing Storable for MaintenanceSchedule
+impl Storable for MaintenanceSchedule {
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Cow::Owned(encode_record(self))
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        decode_record(bytes.as_ref())
+    }
+
+    const BOUND: Bound = Bound::Unbounded;
+}
+
+// Memory management
+thread_local! {
+    static MEMORY_MANAGER: RefCell<MemoryManager<DefaultMemoryImpl>> = RefCell::new(
+        MemoryManager::init(DefaultMemoryImpl::default())
+    );
+
+    static ID_COUNTER: RefCell<IdCell> = RefCell::new(
+        IdCell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(0))), 0)
+            .expect("Cannot create a counter")
+    );
+
+    static AIRSTRIPS: RefCell<StableBTreeMap<u64, Airstrip, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(10)))
+        ));
+
+    static FLIGHTS: RefCell<StableBTreeMap<u64, Flight, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(11)))
+        ));
+
+    static PILOTS: RefCell<StableBTreeMap<u64, Pilot, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(12)))
+        ));
+
+    static MAINTENANCE_SCHEDULES: RefCell<StableBTreeMap<u64, MaintenanceSchedule, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(13)))
+        ));
+
+    static PILOT_SCHEDULES: RefCell<StableBTreeMap<u64, PilotSchedule, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(14)))
+        ));
+
+    static EMERGENCY_PROTOCOLS: RefCell<StableBTreeMap<u64, EmergencyProtocol, Memory>> =
         RefCell::new(StableBTreeMap::init(
             MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(15)))
         ));
 
-    static FUEL_INVENTORIES: RefCell<StableBTreeMap<u64, FuelInventory, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
-        ));
+    static FUEL_INVENTORIES: RefCell<StableBTreeMap<u64, FuelInventory, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(16)))
+        ));
+
+    static REVENUES: RefCell<StableBTreeMap<u64, Revenue, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17)))
+        ));
+
+    static RUNWAYS: RefCell<StableBTreeMap<u64, Runway, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(18)))
+        ));
+
+    static AIRCRAFT: RefCell<StableBTreeMap<u64, Aircraft, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(19)))
+        ));
+
+    static SLOT_RESERVATIONS: RefCell<StableBTreeMap<u64, SlotReservation, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(20)))
+        ));
+
+    static STANDS: RefCell<StableBTreeMap<u64, Stand, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(21)))
+        ));
+
+    static STAND_ASSIGNMENTS: RefCell<StableBTreeMap<u64, StandAssignment, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(22)))
+        ));
+
+    static USER_ROLES: RefCell<StableBTreeMap<PrincipalKey, UserRoles, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(23)))
+        ));
+
+    static AUDIT_LOG: RefCell<StableLog<AuditEntry, Memory, Memory>> = RefCell::new(
+        StableLog::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(24))),
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(25))),
+        )
+        .expect("Cannot create the audit log")
+    );
+
+    static SCHEMA_STATE: RefCell<Cell<SchemaState, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(26))), SchemaState::default())
+            .expect("Cannot create the schema state")
+    );
+
+    static FLIGHTS_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(27)))
+        ));
+
+    static SLOTS_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(28)))
+        ));
+
+    static ASSIGNMENTS_BY_STAND: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(29)))
+        ));
+
+    static SCHEDULES_BY_PILOT: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(30)))
+        ));
+
+    static MAINTENANCE_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(31)))
+        ));
+
+    static PROTOCOLS_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(32)))
+        ));
+
+    static FUEL_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(33)))
+        ));
+
+    static REVENUE_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(34)))
+        ));
+
+    static FTL_LIMITS: RefCell<Cell<FtlLimits, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(35))), FtlLimits::default())
+            .expect("Cannot create the FTL limits")
+    );
+
+    static SCHEDULES_BY_FLIGHT: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(36)))
+        ));
+
+    static LOGBOOK: RefCell<StableBTreeMap<u64, LogbookEntry, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(37)))
+        ));
+
+    static LOGBOOK_BY_PILOT: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(38)))
+        ));
+
+    static RECENCY_POLICY: RefCell<Cell<RecencyPolicy, Memory>> = RefCell::new(
+        Cell::init(MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(39))), RecencyPolicy::default())
+            .expect("Cannot create the recency policy")
+    );
+
+    static UNAVAILABILITY: RefCell<StableBTreeMap<u64, Unavailability, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(40)))
+        ));
+
+    static UNAVAILABILITY_BY_PILOT: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(41)))
+        ));
+
+    static WEEKLY_AVAILABILITY: RefCell<StableBTreeMap<u64, WeeklyAvailability, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(42)))
+        ));
+
+    static EXPENSES: RefCell<StableBTreeMap<u64, Expense, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(43)))
+        ));
+
+    static EXPENSES_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(44)))
+        ));
+
+    static MAINTENANCE_PLANS: RefCell<StableBTreeMap<u64, MaintenancePlan, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(45)))
+        ));
+
+    static PROTOCOL_REVISIONS: RefCell<StableBTreeMap<u64, ProtocolRevision, Memory>> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(46)))
+        ));
+
+    static REVISIONS_BY_PROTOCOL: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(47)))
+        ));
+
+    static FLIGHTS_BY_AIRCRAFT: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(48)))
+        ));
+
+    static RUNWAYS_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(49)))
+        ));
+
+    static STANDS_BY_AIRSTRIP: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(50)))
+        ));
+
+    static FLIGHTS_BY_RUNWAY: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(51)))
+        ));
+
+    static AIRCRAFT_BY_BASE: RefCell<Index> =
+        RefCell::new(StableBTreeMap::init(
+            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(52)))
+        ));
+}
+
+// Functions
+
+// Allocates a record id; every kind of record draws from the one counter
+fn next_id() -> u64 {
+    ID_COUNTER.with(|counter| {
+        let current_value = *counter.borrow().get();
+        counter.borrow_mut().set(current_value + 1).expect("Counter increment failed");
+        current_value
+    })
+}
+
+// Upgrades and migrations
+
+#[ic_cdk::init]
+fn init() {
+    SCHEMA_STATE.with(|state| {
+        state
+            .borrow_mut()
+            .set(SchemaState {
+                version: SCHEMA_VERSION,
+                last_migration: None,
+            })
+            .expect("Cannot record the schema version");
+    });
+}
+
+#[ic_cdk::post_upgrade]
+fn post_upgrade() {
+    run_migrations();
+}
+
+// Runs every migration newer than the stored schema version, in order
+fn run_migrations() {
+    let state = SCHEMA_STATE.with(|state| state.borrow().get().clone());
+    if state.version >= SCHEMA_VERSION {
+        return;
+    }
+
+    let started_at = time();
+    let mut steps = Vec::new();
+    let mut records_migrated = 0;
+    for migration in MIGRATIONS.iter().filter(|m| m.to_version > state.version) {
+        records_migrated += (migration.run)();
+        steps.push(format!("v{}: {}", migration.to_version, migration.description));
+    }
+
+    let report = MigrationReport {
+        from_version: state.version,
+        to_version: SCHEMA_VERSION,
+        steps,
+        records_migrated,
+        started_at,
+        completed_at: time(),
+    };
+
+    SCHEMA_STATE.with(|state| {
+        state
+            .borrow_mut()
+            .set(SchemaState {
+                version: SCHEMA_VERSION,
+                last_migration: Some(report),
+            })
+            .expect("Cannot record the schema version");
+    });
+}
+
+// Reads every record (decoding whatever version it was written in) and writes it back in the current layout
+fn rewrite_records<K: Storable + Ord + Clone, V: Storable>(map: &mut StableBTreeMap<K, V, Memory>) -> u64 {
+    let keys: Vec<K> = map.iter().map(|(key, _)| key).collect();
+    for key in &keys {
+        let value = map.get(key).expect("Record disappeared during migration");
+        map.insert(key.clone(), value);
+    }
+    keys.len() as u64
+}
+
+fn rewrite_all_records() -> u64 {
+    AIRSTRIPS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + RUNWAYS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + STANDS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + STAND_ASSIGNMENTS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + SLOT_RESERVATIONS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + FLIGHTS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + AIRCRAFT.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + PILOTS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + PILOT_SCHEDULES.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + MAINTENANCE_SCHEDULES.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + EMERGENCY_PROTOCOLS.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + FUEL_INVENTORIES.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + REVENUES.with(|m| rewrite_records(&mut m.borrow_mut()))
+        + USER_ROLES.with(|m| rewrite_records(&mut m.borrow_mut()))
+}
+
+// Secondary indexes
+//
+// Each index maps IndexKey { owner, time, id } to () so per-airstrip, per-pilot
+// and per-stand lookups are range scans instead of walks over the whole map.
+// Every insert of a new record must add its index entry alongside.
+
+// Ids indexed under `owner` with a time in [start, end], oldest first
+fn index_ids(index: &Index, owner: u64, start: u64, end: u64) -> Vec<u64> {
+    if start > end {
+        return Vec::new();
+    }
+    index.range(IndexKey::span(owner, start, end)).map(|(key, _)| key.id).collect()
+}
+
+fn index_flight(flight: &Flight) {
+    let key = IndexKey::new(flight.airstrip_id, flight.movement_time(), flight.id);
+    FLIGHTS_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+    let key = IndexKey::new(flight.aircraft_id, flight.departure_time, flight.id);
+    FLIGHTS_BY_AIRCRAFT.with(|index| index.borrow_mut().insert(key, ()));
+    if let Some(runway_id) = flight.runway_id {
+        let key = IndexKey::new(runway_id, flight.movement_time(), flight.id);
+        FLIGHTS_BY_RUNWAY.with(|index| index.borrow_mut().insert(key, ()));
+    }
+}
+
+fn unindex_flight(flight: &Flight) {
+    let key = IndexKey::new(flight.airstrip_id, flight.movement_time(), flight.id);
+    FLIGHTS_BY_AIRSTRIP.with(|index| index.borrow_mut().remove(&key));
+    let key = IndexKey::new(flight.aircraft_id, flight.departure_time, flight.id);
+    FLIGHTS_BY_AIRCRAFT.with(|index| index.borrow_mut().remove(&key));
+    if let Some(runway_id) = flight.runway_id {
+        let key = IndexKey::new(runway_id, flight.movement_time(), flight.id);
+        FLIGHTS_BY_RUNWAY.with(|index| index.borrow_mut().remove(&key));
+    }
+}
+
+fn index_runway(runway: &Runway) {
+    let key = IndexKey::new(runway.airstrip_id, runway.created_at, runway.id);
+    RUNWAYS_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn unindex_runway(runway: &Runway) {
+    let key = IndexKey::new(runway.airstrip_id, runway.created_at, runway.id);
+    RUNWAYS_BY_AIRSTRIP.with(|index| index.borrow_mut().remove(&key));
+}
+
+fn index_stand(stand: &Stand) {
+    let key = IndexKey::new(stand.airstrip_id, stand.created_at, stand.id);
+    STANDS_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn unindex_stand(stand: &Stand) {
+    let key = IndexKey::new(stand.airstrip_id, stand.created_at, stand.id);
+    STANDS_BY_AIRSTRIP.with(|index| index.borrow_mut().remove(&key));
+}
+
+fn index_aircraft(aircraft: &Aircraft) {
+    if let Some(airstrip_id) = aircraft.base_airstrip_id {
+        let key = IndexKey::new(airstrip_id, aircraft.created_at, aircraft.id);
+        AIRCRAFT_BY_BASE.with(|index| index.borrow_mut().insert(key, ()));
+    }
+}
+
+fn unindex_aircraft(aircraft: &Aircraft) {
+    if let Some(airstrip_id) = aircraft.base_airstrip_id {
+        let key = IndexKey::new(airstrip_id, aircraft.created_at, aircraft.id);
+        AIRCRAFT_BY_BASE.with(|index| index.borrow_mut().remove(&key));
+    }
+}
+
+fn index_slot(slot: &SlotReservation) {
+    let key = IndexKey::new(slot.airstrip_id, slot.slot_start, slot.id);
+    SLOTS_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_stand_assignment(assignment: &StandAssignment) {
+    let key = IndexKey::new(assignment.stand_id, assignment.start_time, assignment.id);
+    ASSIGNMENTS_BY_STAND.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_pilot_schedule(schedule: &PilotSchedule) {
+    let key = IndexKey::new(schedule.pilot_id, schedule.start_time, schedule.id);
+    SCHEDULES_BY_PILOT.with(|index| index.borrow_mut().insert(key, ()));
+    let key = IndexKey::new(schedule.flight_id, schedule.start_time, schedule.id);
+    SCHEDULES_BY_FLIGHT.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn unindex_pilot_schedule(schedule: &PilotSchedule) {
+    let key = IndexKey::new(schedule.pilot_id, schedule.start_time, schedule.id);
+    SCHEDULES_BY_PILOT.with(|index| index.borrow_mut().remove(&key));
+    let key = IndexKey::new(schedule.flight_id, schedule.start_time, schedule.id);
+    SCHEDULES_BY_FLIGHT.with(|index| index.borrow_mut().remove(&key));
+}
+
+fn index_logbook_entry(entry: &LogbookEntry) {
+    let key = IndexKey::new(entry.pilot_id, entry.arrival_time, entry.id);
+    LOGBOOK_BY_PILOT.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_unavailability(block: &Unavailability) {
+    let key = IndexKey::new(block.pilot_id, block.start_time, block.id);
+    UNAVAILABILITY_BY_PILOT.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_maintenance(maintenance: &MaintenanceSchedule) {
+    let key = IndexKey::new(maintenance.airstrip_id, maintenance.date, maintenance.id);
+    MAINTENANCE_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn unindex_maintenance(maintenance: &MaintenanceSchedule) {
+    let key = IndexKey::new(maintenance.airstrip_id, maintenance.date, maintenance.id);
+    MAINTENANCE_BY_AIRSTRIP.with(|index| index.borrow_mut().remove(&key));
+}
+
+fn index_protocol(protocol: &EmergencyProtocol) {
+    let key = IndexKey::new(protocol.airstrip_id, protocol.created_at, protocol.id);
+    PROTOCOLS_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_protocol_revision(revision: &ProtocolRevision) {
+    let key = IndexKey::new(revision.protocol_id, revision.revision, revision.id);
+    REVISIONS_BY_PROTOCOL.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_fuel_inventory(inventory: &FuelInventory) {
+    let key = IndexKey::new(inventory.airstrip_id, inventory.last_updated, inventory.id);
+    FUEL_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_revenue(revenue: &Revenue) {
+    let key = IndexKey::new(revenue.airstrip_id, revenue.transaction_date, revenue.id);
+    REVENUE_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+fn index_expense(expense: &Expense) {
+    let key = IndexKey::new(expense.airstrip_id, expense.transaction_date, expense.id);
+    EXPENSES_BY_AIRSTRIP.with(|index| index.borrow_mut().insert(key, ()));
+}
+
+// Indexes every existing record; the index memories are empty before this runs
+fn rebuild_indexes() -> u64 {
+    let mut indexed = 0;
+    FLIGHTS.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_flight(&record);
+            indexed += 1;
+        }
+    });
+    SLOT_RESERVATIONS.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_slot(&record);
+            indexed += 1;
+        }
+    });
+    STAND_ASSIGNMENTS.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_stand_assignment(&record);
+            indexed += 1;
+        }
+    });
+    PILOT_SCHEDULES.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_pilot_schedule(&record);
+            indexed += 1;
+        }
+    });
+    MAINTENANCE_SCHEDULES.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_maintenance(&record);
+            indexed += 1;
+        }
+    });
+    EMERGENCY_PROTOCOLS.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_protocol(&record);
+            indexed += 1;
+        }
+    });
+    FUEL_INVENTORIES.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_fuel_inventory(&record);
+            indexed += 1;
+        }
+    });
+    REVENUES.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_revenue(&record);
+            indexed += 1;
+        }
+    });
+    indexed
+}
+
+fn index_schedules_by_flight() -> u64 {
+    PILOT_SCHEDULES.with(|schedules| {
+        let mut indexed = 0;
+        for (_, schedule) in schedules.borrow().iter() {
+            let key = IndexKey::new(schedule.flight_id, schedule.start_time, schedule.id);
+            SCHEDULES_BY_FLIGHT.with(|index| index.borrow_mut().insert(key, ()));
+            indexed += 1;
+        }
+        indexed
+    })
+}
+
+fn index_flights_by_aircraft() -> u64 {
+    FLIGHTS.with(|flights| {
+        let mut indexed = 0;
+        for (_, flight) in flights.borrow().iter() {
+            let key = IndexKey::new(flight.aircraft_id, flight.departure_time, flight.id);
+            FLIGHTS_BY_AIRCRAFT.with(|index| index.borrow_mut().insert(key, ()));
+            indexed += 1;
+        }
+        indexed
+    })
+}
+
+fn index_airstrip_resources() -> u64 {
+    let mut indexed = 0;
+    RUNWAYS.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_runway(&record);
+            indexed += 1;
+        }
+    });
+    STANDS.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_stand(&record);
+            indexed += 1;
+        }
+    });
+    AIRCRAFT.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            index_aircraft(&record);
+            indexed += 1;
+        }
+    });
+    FLIGHTS.with(|records| {
+        for (_, record) in records.borrow().iter() {
+            if let Some(runway_id) = record.runway_id {
+                let key = IndexKey::new(runway_id, record.movement_time(), record.id);
+                FLIGHTS_BY_RUNWAY.with(|index| index.borrow_mut().insert(key, ()));
+                indexed += 1;
+            }
+        }
+    });
+    indexed
+}
+
+fn rewrite_maintenance_schedules() -> u64 {
+    MAINTENANCE_SCHEDULES.with(|m| rewrite_records(&mut m.borrow_mut()))
+}
+
+// Rewrites protocols with their typed layout and records each one's current text as revision 1
+fn migrate_emergency_protocols() -> u64 {
+    let protocols: Vec<EmergencyProtocol> = EMERGENCY_PROTOCOLS.with(|protocols| {
+        let mut protocols = protocols.borrow_mut();
+        rewrite_records(&mut protocols);
+        protocols.iter().map(|(_, protocol)| protocol).collect()
+    });
+    for protocol in &protocols {
+        insert_protocol_revision(protocol, "Recorded before revisions were kept".to_string());
+    }
+    protocols.len() as u64
+}
+
+// Schema version of this build, of the stored data, and the last migration run
+#[ic_cdk::query]
+fn get_schema_info() -> SchemaInfo {
+    let state = SCHEMA_STATE.with(|state| state.borrow().get().clone());
+    SchemaInfo {
+        code_version: SCHEMA_VERSION,
+        stored_version: state.version,
+        pending_migrations: MIGRATIONS
+            .iter()
+            .filter(|m| m.to_version > state.version)
+            .map(|m| format!("v{}: {}", m.to_version, m.description))
+            .collect(),
+        last_migration: state.last_migration,
+    }
+}
+
+// Input limits
+//
+// Records have no size cap in stable memory, but inputs are still bounded so
+// one call cannot bloat storage or the audit log with arbitrarily large text.
+const MAX_NAME_LEN: usize = 100;
+const MAX_TEXT_LEN: usize = 4_000;
+const MAX_LIST_LEN: usize = 50;
+
+// Rejects text longer than `max` characters
+fn check_len(field: &str, value: &str, max: usize) -> Result<(), Message> {
+    if value.chars().count() > max {
+        return Err(Message::InvalidPayload(format!(
+            "{} cannot be longer than {} characters",
+            field, max
+        )));
+    }
+    Ok(())
+}
+
+// Rejects lists with too many entries, or with an entry longer than `max_item` characters
+fn check_list(field: &str, items: &[String], max_item: usize) -> Result<(), Message> {
+    if items.len() > MAX_LIST_LEN {
+        return Err(Message::InvalidPayload(format!(
+            "{} cannot have more than {} entries",
+            field, MAX_LIST_LEN
+        )));
+    }
+    for item in items {
+        check_len(field, item, max_item)?;
+    }
+    Ok(())
+}
+
+// Accepts numbers such as "+254 712 345-678" or "(020) 123 4567": an optional
+// leading +, then 3 to 15 digits with spaces, hyphens or brackets between them
+fn check_phone_number(number: &str) -> Result<(), Message> {
+    let number = number.trim();
+    let digits = number.chars().filter(|c| c.is_ascii_digit()).count();
+    let body = number.strip_prefix('+').unwrap_or(number);
+    let well_formed = body
+        .chars()
+        .all(|c| c.is_ascii_digit() || c == ' ' || c == '-' || c == '(' || c == ')');
+    if !well_formed || !(3..=15).contains(&digits) {
+        return Err(Message::InvalidPayload(format!("Invalid phone number: {}", number)));
+    }
+    Ok(())
+}
+
+// Pagination
+
+// Keys a list query can page over; a cursor records the last key a page took
+trait PageKey: Storable + Ord + Clone {
+    fn cursor(&self) -> String;
+    // The key a cursor names, within the same range as `self`; None if malformed
+    fn at_cursor(&self, cursor: &str) -> Option<Self>;
+}
+
+impl PageKey for u64 {
+    fn cursor(&self) -> String {
+        position_cursor((0, *self))
+    }
+
+    fn at_cursor(&self, cursor: &str) -> Option<Self> {
+        parse_position(cursor).map(|(_, id)| id)
+    }
+}
+
+impl PageKey for IndexKey {
+    fn cursor(&self) -> String {
+        position_cursor((self.time, self.id))
+    }
+
+    fn at_cursor(&self, cursor: &str) -> Option<Self> {
+        parse_position(cursor).map(|(time, id)| IndexKey::new(self.owner, time, id))
+    }
+}
+
+impl PageKey for PrincipalKey {
+    fn cursor(&self) -> String {
+        self.0.to_text()
+    }
+
+    fn at_cursor(&self, cursor: &str) -> Option<Self> {
+        Principal::from_text(cursor).ok().map(PrincipalKey)
+    }
+}
+
+// Records and index entries are positioned by (time, id), written as two hex numbers
+fn position_cursor((time, id): (u64, u64)) -> String {
+    format!("{:x}.{:x}", time, id)
+}
+
+fn parse_position(cursor: &str) -> Option<(u64, u64)> {
+    let (time, id) = cursor.split_once('.')?;
+    Some((u64::from_str_radix(time, 16).ok()?, u64::from_str_radix(id, 16).ok()?))
+}
+
+impl PageRequest {
+    fn page_size(&self) -> usize {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE) as usize
+    }
+
+    // Key the previous page stopped at, in the range starting at `first`
+    fn after<K: PageKey>(&self, first: &K) -> Result<Option<K>, Message> {
+        match &self.cursor {
+            Some(cursor) => first
+                .at_cursor(cursor)
+                .map(Some)
+                .ok_or_else(|| Message::InvalidPayload("Invalid page cursor".to_string())),
+            None => Ok(None),
+        }
+    }
+}
+
+// Walks the keys in `range` in the requested order, resuming after the page
+// cursor, and keeps what `pick` returns until the page is full
+fn collect_page<K: PageKey, V: Storable, T>(
+    map: &StableBTreeMap<K, V, Memory>,
+    range: RangeInclusive<K>,
+    page: &PageRequest,
+    mut pick: impl FnMut(&K, V) -> Option<T>,
+) -> Result<Page<T>, Message> {
+    let limit = page.page_size();
+    let (first, last) = range.into_inner();
+    let empty = Page {
+        items: Vec::new(),
+        next_cursor: None,
+    };
+    if first > last {
+        return Ok(empty);
+    }
+
+    let bounds = match (page.after(&first)?, page.order) {
+        (None, _) => (RangeBound::Included(first), RangeBound::Included(last)),
+        (Some(after), SortOrder::Ascending) if after >= last => return Ok(empty),
+        (Some(after), SortOrder::Ascending) if after >= first => (RangeBound::Excluded(after), RangeBound::Included(last)),
+        (Some(before), SortOrder::Descending) if before <= first => return Ok(empty),
+        (Some(before), SortOrder::Descending) if before <= last => (RangeBound::Included(first), RangeBound::Excluded(before)),
+        (Some(_), _) => (RangeBound::Included(first), RangeBound::Included(last)),
+    };
+    let entries: Box<dyn Iterator<Item = (K, V)>> = match page.order {
+        SortOrder::Ascending => Box::new(map.range(bounds)),
+        SortOrder::Descending => Box::new(map.range(bounds).rev()),
+    };
+
+    let mut items = Vec::new();
+    let mut last_taken: Option<K> = None;
+    let mut next_cursor = None;
+    for (key, value) in entries {
+        if items.len() == limit {
+            next_cursor = last_taken.map(|key| key.cursor());
+            break;
+        }
+        if let Some(item) = pick(&key, value) {
+            items.push(item);
+            last_taken = Some(key);
+        }
+    }
+
+    Ok(Page { items, next_cursor })
+}
+
+// Access control
+
+// Returns the caller if it is a system admin (controllers always are) or holds a role accepted by `allowed`
+fn authorize(allowed: impl Fn(&Role) -> bool) -> Result<Principal, Message> {
+    let caller = caller();
+    if caller == Principal::anonymous() {
+        return Err(Message::Unauthorized("Anonymous callers are not allowed".to_string()));
+    }
+    if is_controller(&caller) {
+        return Ok(caller);
+    }
+
+    let roles = USER_ROLES
+        .with(|users| users.borrow().get(&PrincipalKey(caller)))
+        .map(|user| user.roles)
+        .unwrap_or_default();
+    if roles.iter().any(|role| *role == Role::SystemAdmin || allowed(role)) {
+        Ok(caller)
+    } else {
+        Err(Message::Unauthorized("Caller does not have a role that permits this action".to_string()))
+    }
+}
+
+fn require_admin() -> Result<Principal, Message> {
+    authorize(|_| false)
+}
+
+// Audit trail
+
+fn record_audit<T: serde::Serialize>(
+    action: AuditAction,
+    entity: EntityKind,
+    entity_id: u64,
+    before: Option<&T>,
+    after: Option<&T>,
+) {
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        let entry = AuditEntry {
+            id: log.len(),
+            caller: caller(),
+            timestamp: time(),
+            action,
+            entity,
+            entity_id,
+            before: before.and_then(|value| serde_json::to_string(value).ok()),
+            after: after.and_then(|value| serde_json::to_string(value).ok()),
+        };
+        log.append(&entry).expect("Cannot append to the audit log");
+    });
+}
+
+// Audit log entries matching the query, newest first
+#[ic_cdk::query]
+fn get_audit_log(query: AuditQuery) -> Result<AuditPage, Message> {
+    require_admin()?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_AUDIT_PAGE_SIZE).clamp(1, MAX_AUDIT_PAGE_SIZE) as usize;
+    let mut entries = Vec::new();
+    let mut next_cursor = None;
+
+    AUDIT_LOG.with(|log| {
+        let log = log.borrow();
+        let mut index = query.cursor.unwrap_or(log.len()).min(log.len());
+        while index > 0 {
+            let entry = log.get(index - 1).expect("Audit log entry missing");
+            // Entries are appended in time order, so nothing older can match
+            if query.start_time.is_some_and(|start| entry.timestamp < start) {
+                break;
+            }
+            if entries.len() == limit {
+                next_cursor = Some(index);
+                break;
+            }
+            index -= 1;
+
+            let matches = query.entity.is_none_or(|entity| entry.entity == entity)
+                && query.entity_id.is_none_or(|id| entry.entity_id == id)
+                && query.caller.is_none_or(|caller| entry.caller == caller)
+                && query.end_time.is_none_or(|end| entry.timestamp <= end);
+            if matches {
+                entries.push(entry);
+            }
+        }
+    });
+
+    Ok(AuditPage { entries, next_cursor })
+}
+
+// Grant Role
+#[ic_cdk::update]
+fn grant_role(principal: Principal, role: Role) -> Result<UserRoles, Message> {
+    require_admin()?;
+
+    if principal == Principal::anonymous() {
+        return Err(Message::InvalidPayload("Roles cannot be granted to the anonymous principal".to_string()));
+    }
+    match role {
+        Role::AirstripManager(airstrip_id) => {
+            get_active_airstrip(airstrip_id)?;
+        }
+        Role::Pilot(pilot_id) => {
+            if !PILOTS.with(|pilots| pilots.borrow().contains_key(&pilot_id)) {
+                return Err(Message::NotFound("Pilot not found".to_string()));
+            }
+        }
+        Role::SystemAdmin | Role::Dispatcher | Role::Finance => {}
+    }
+
+    let mut user = USER_ROLES
+        .with(|users| users.borrow().get(&PrincipalKey(principal)))
+        .unwrap_or(UserRoles {
+            principal,
+            roles: Vec::new(),
+            updated_at: 0,
+        });
+    if user.roles.contains(&role) {
+        return Err(Message::InvalidPayload("Principal already holds this role".to_string()));
+    }
+    user.roles.push(role);
+    user.updated_at = time();
+
+    USER_ROLES.with(|users| {
+        users.borrow_mut().insert(PrincipalKey(principal), user.clone());
+    });
+
+    Ok(user)
+}
+
+// Revoke Role
+#[ic_cdk::update]
+fn revoke_role(principal: Principal, role: Role) -> Result<UserRoles, Message> {
+    require_admin()?;
+
+    let mut user = match USER_ROLES.with(|users| users.borrow().get(&PrincipalKey(principal))) {
+        Some(user) if user.roles.contains(&role) => user,
+        _ => return Err(Message::NotFound("Principal does not hold this role".to_string())),
+    };
+    user.roles.retain(|r| *r != role);
+    user.updated_at = time();
+
+    USER_ROLES.with(|users| {
+        let mut users = users.borrow_mut();
+        if user.roles.is_empty() {
+            users.remove(&PrincipalKey(principal));
+        } else {
+            users.insert(PrincipalKey(principal), user.clone());
+        }
+    });
+
+    Ok(user)
+}
+
+// Roles held by the caller
+#[ic_cdk::query]
+fn get_my_roles() -> UserRoles {
+    let caller = caller();
+    USER_ROLES
+        .with(|users| users.borrow().get(&PrincipalKey(caller)))
+        .unwrap_or(UserRoles {
+            principal: caller,
+            roles: Vec::new(),
+            updated_at: 0,
+        })
+}
+
+// Every role assignment, for admins
+#[ic_cdk::query]
+fn list_role_assignments(page: PageRequest) -> Result<Page<UserRoles>, Message> {
+    require_admin()?;
+    // Principals are at most 29 bytes and sort bytewise
+    let every = PrincipalKey(Principal::from_slice(&[]))..=PrincipalKey(Principal::from_slice(&[u8::MAX; 29]));
+    USER_ROLES.with(|users| collect_page(&users.borrow(), every, &page, |_, user| Some(user)))
+}
+
+// Create Airstrip
+#[ic_cdk::update]
+fn create_airstrip(payload: CreateAirstripPayload) -> Result<Airstrip, Message> {
+    require_admin()?;
+
+    if payload.name.is_empty() || payload.contact.is_empty() || payload.email.is_empty() {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Name", &payload.name, MAX_NAME_LEN)?;
+    check_len("Location", &payload.location, MAX_NAME_LEN)?;
+    check_len("Contact", &payload.contact, MAX_NAME_LEN)?;
+    check_len("Email", &payload.email, MAX_NAME_LEN)?;
+    if let Some(coordinates) = &payload.coordinates {
+        coordinates.validate()?;
+    }
+
+    let airstrip_id = next_id();
+
+    let airstrip = Airstrip {
+        id: airstrip_id,
+        name: payload.name,
+        location: payload.location,
+        coordinates: payload.coordinates,
+        contact: payload.contact,
+        email: payload.email,
+        runway_length: payload.runway_length,
+        capacity: payload.capacity,
+        movements_per_hour: payload.movements_per_hour,
+        created_at: time(),
+        archived_at: None,
+    };
+
+    AIRSTRIPS.with(|airstrips| {
+        airstrips.borrow_mut().insert(airstrip_id, airstrip.clone());
+    });
+
+    record_audit(AuditAction::Create, EntityKind::Airstrip, airstrip_id, None, Some(&airstrip));
+
+    Ok(airstrip)
+}
+
+// Get Airstrip
+#[ic_cdk::query]
+fn get_airstrip(airstrip_id: u64) -> Result<Airstrip, Message> {
+    match AIRSTRIPS.with(|airstrips| airstrips.borrow().get(&airstrip_id)) {
+        Some(airstrip) => Ok(airstrip),
+        None => Err(Message::NotFound("Airstrip not found".to_string())),
+    }
+}
+
+// List Airstrips
+#[ic_cdk::query]
+fn list_airstrips(include_archived: bool, page: PageRequest) -> Result<Page<Airstrip>, Message> {
+    AIRSTRIPS.with(|airstrips| {
+        collect_page(&airstrips.borrow(), 0..=u64::MAX, &page, |_, airstrip| {
+            (include_archived || airstrip.archived_at.is_none()).then_some(airstrip)
+        })
+    })
+}
+
+// Update Airstrip
+#[ic_cdk::update]
+fn update_airstrip(airstrip_id: u64, payload: UpdateAirstripPayload) -> Result<Airstrip, Message> {
+    authorize(|role| role.manages(airstrip_id))?;
+    let mut airstrip = get_active_airstrip(airstrip_id)?;
+    let before = airstrip.clone();
+
+    if let Some(name) = payload.name {
+        if name.is_empty() {
+            return Err(Message::InvalidPayload("Name cannot be empty".to_string()));
+        }
+        check_len("Name", &name, MAX_NAME_LEN)?;
+        airstrip.name = name;
+    }
+    if let Some(contact) = payload.contact {
+        if contact.is_empty() {
+            return Err(Message::InvalidPayload("Contact cannot be empty".to_string()));
+        }
+        check_len("Contact", &contact, MAX_NAME_LEN)?;
+        airstrip.contact = contact;
+    }
+    if let Some(email) = payload.email {
+        if email.is_empty() {
+            return Err(Message::InvalidPayload("Email cannot be empty".to_string()));
+        }
+        check_len("Email", &email, MAX_NAME_LEN)?;
+        airstrip.email = email;
+    }
+    if let Some(location) = payload.location {
+        check_len("Location", &location, MAX_NAME_LEN)?;
+        airstrip.location = location;
+    }
+    if let Some(coordinates) = payload.coordinates {
+        coordinates.validate()?;
+        airstrip.coordinates = Some(coordinates);
+    }
+    if let Some(runway_length) = payload.runway_length {
+        airstrip.runway_length = runway_length;
+    }
+    if let Some(capacity) = payload.capacity {
+        airstrip.capacity = capacity;
+    }
+    if let Some(movements_per_hour) = payload.movements_per_hour {
+        airstrip.movements_per_hour = movements_per_hour;
+    }
+
+    AIRSTRIPS.with(|airstrips| {
+        airstrips.borrow_mut().insert(airstrip_id, airstrip.clone());
+    });
+
+    record_audit(AuditAction::Update, EntityKind::Airstrip, airstrip_id, Some(&before), Some(&airstrip));
+
+    Ok(airstrip)
+}
+
+// Delete Airstrip (only allowed once nothing references it any more)
+#[ic_cdk::update]
+fn delete_airstrip(airstrip_id: u64) -> Result<Airstrip, Message> {
+    require_admin()?;
+
+    if !AIRSTRIPS.with(|airstrips| airstrips.borrow().contains_key(&airstrip_id)) {
+        return Err(Message::NotFound("Airstrip not found".to_string()));
+    }
+
+    let references = airstrip_references(airstrip_id);
+    if !references.is_empty() {
+        return Err(Message::Error(format!(
+            "Airstrip is still referenced by {}; archive it instead",
+            references.join(", ")
+        )));
+    }
+
+    let airstrip = AIRSTRIPS
+        .with(|airstrips| airstrips.borrow_mut().remove(&airstrip_id))
+        .expect("Airstrip disappeared during delete");
+
+    record_audit(AuditAction::Delete, EntityKind::Airstrip, airstrip_id, Some(&airstrip), None);
+
+    Ok(airstrip)
+}
+
+// Archive Airstrip together with everything that references it. A call archives
+// at most MAX_ARCHIVE_RECORDS records; while some remain the airstrip stays
+// active and `resume_from` says where the next call picks up. Flights that have
+// not departed give up their slot, stand and crew first.
+#[ic_cdk::update]
+fn archive_airstrip(airstrip_id: u64, cursor: Option<ArchiveCursor>) -> Result<AirstripArchive, Message> {
+    require_admin()?;
+    let mut airstrip = get_active_airstrip(airstrip_id)?;
+    let before = airstrip.clone();
+    let archived_at = time();
+
+    let first_stage = match cursor {
+        Some(cursor) => ARCHIVE_ORDER
+            .iter()
+            .position(|entity| *entity == cursor.entity)
+            .ok_or_else(|| Message::InvalidPayload("Invalid archive cursor".to_string()))?,
+        None => 0,
+    };
+
+    let mut visited = 0;
+    let mut archived = 0;
+    for &entity in &ARCHIVE_ORDER[first_stage..] {
+        let start = match cursor {
+            Some(cursor) if cursor.entity == entity => IndexKey::new(airstrip_id, cursor.time, cursor.id),
+            _ => IndexKey::new(airstrip_id, 0, 0),
+        };
+        let end = IndexKey::new(airstrip_id, u64::MAX, u64::MAX);
+        let keys: Vec<IndexKey> = airstrip_index(entity).with(|index| {
+            index
+                .borrow()
+                .range(start..=end)
+                .take(MAX_ARCHIVE_RECORDS - visited + 1)
+                .map(|(key, _)| key)
+                .collect()
+        });
+
+        for key in keys {
+            if visited == MAX_ARCHIVE_RECORDS {
+                return Ok(AirstripArchive {
+                    airstrip,
+                    archived,
+                    resume_from: Some(ArchiveCursor { entity, time: key.time, id: key.id }),
+                });
+            }
+            visited += 1;
+            if archive_record(entity, key.id, archived_at) {
+                archived += 1;
+            }
+        }
+    }
+
+    airstrip.archived_at = Some(archived_at);
+    AIRSTRIPS.with(|airstrips| {
+        airstrips.borrow_mut().insert(airstrip_id, airstrip.clone());
+    });
+
+    record_audit(AuditAction::Archive, EntityKind::Airstrip, airstrip_id, Some(&before), Some(&airstrip));
+
+    Ok(AirstripArchive {
+        airstrip,
+        archived,
+        resume_from: None,
+    })
+}
+
+// Per-airstrip index of each kind of record archive_airstrip works through
+fn airstrip_index(entity: EntityKind) -> &'static LocalKey<RefCell<Index>> {
+    match entity {
+        EntityKind::Flight => &FLIGHTS_BY_AIRSTRIP,
+        EntityKind::MaintenanceSchedule => &MAINTENANCE_BY_AIRSTRIP,
+        EntityKind::FuelInventory => &FUEL_BY_AIRSTRIP,
+        EntityKind::Revenue => &REVENUE_BY_AIRSTRIP,
+        EntityKind::Expense => &EXPENSES_BY_AIRSTRIP,
+        EntityKind::EmergencyProtocol => &PROTOCOLS_BY_AIRSTRIP,
+        _ => unreachable!("{:?} records are not archived with their airstrip", entity),
+    }
+}
+
+// Stamps one record archived, returning false when it already was
+fn archive_record(entity: EntityKind, id: u64, archived_at: u64) -> bool {
+    match entity {
+        EntityKind::Flight => match stamp_archived(&FLIGHTS, entity, id, archived_at, |flight| &mut flight.archived_at) {
+            Some(before) if before.awaiting_departure() => {
+                release_flight(&before);
+                true
+            }
+            Some(_) => true,
+            None => false,
+        },
+        EntityKind::MaintenanceSchedule => {
+            stamp_archived(&MAINTENANCE_SCHEDULES, entity, id, archived_at, |schedule| &mut schedule.archived_at)
+                .is_some()
+        }
+        EntityKind::FuelInventory => {
+            stamp_archived(&FUEL_INVENTORIES, entity, id, archived_at, |inventory| &mut inventory.archived_at).is_some()
+        }
+        EntityKind::Revenue => {
+            stamp_archived(&REVENUES, entity, id, archived_at, |revenue| &mut revenue.archived_at).is_some()
+        }
+        EntityKind::Expense => {
+            stamp_archived(&EXPENSES, entity, id, archived_at, |expense| &mut expense.archived_at).is_some()
+        }
+        EntityKind::EmergencyProtocol => {
+            stamp_archived(&EMERGENCY_PROTOCOLS, entity, id, archived_at, |protocol| &mut protocol.archived_at)
+                .is_some()
+        }
+        _ => unreachable!("{:?} records are not archived with their airstrip", entity),
+    }
+}
+
+// Sets a live record's archived_at and audits it, returning the record as it was
+fn stamp_archived<T: Storable + Clone + serde::Serialize>(
+    records: &'static LocalKey<RefCell<StableBTreeMap<u64, T, Memory>>>,
+    entity: EntityKind,
+    id: u64,
+    archived_at: u64,
+    stamp: fn(&mut T) -> &mut Option<u64>,
+) -> Option<T> {
+    records.with(|records| {
+        let mut records = records.borrow_mut();
+        let mut before = records.get(&id)?;
+        if stamp(&mut before).is_some() {
+            return None;
+        }
+        let mut record = before.clone();
+        *stamp(&mut record) = Some(archived_at);
+        records.insert(id, record.clone());
+        record_audit(AuditAction::Archive, entity, id, Some(&before), Some(&record));
+        Some(before)
+    })
+}
+
+// Airstrips within a radius of a point, closest first
+#[ic_cdk::query]
+fn find_airstrips_within(latitude: f64, longitude: f64, radius_km: f64) -> Result<Vec<AirstripDistance>, Message> {
+    Coordinates { latitude, longitude, elevation: 0 }.validate()?;
+    if !radius_km.is_finite() || radius_km <= 0.0 {
+        return Err(Message::InvalidPayload("Radius must be a positive number".to_string()));
+    }
+
+    let mut results = airstrip_distances(latitude, longitude);
+    results.retain(|entry| entry.distance_km <= radius_km);
+    Ok(results)
+}
+
+// Nearest airstrips offering at least the given runway length, closest first
+#[ic_cdk::query]
+fn find_nearest_airstrips(
+    latitude: f64,
+    longitude: f64,
+    limit: u64,
+    min_runway_length: u64,
+) -> Result<Vec<AirstripDistance>, Message> {
+    Coordinates { latitude, longitude, elevation: 0 }.validate()?;
+
+    let mut results = airstrip_distances(latitude, longitude);
+    results.retain(|entry| usable_runway_length(&entry.airstrip) >= min_runway_length);
+    results.truncate(limit as usize);
+    Ok(results)
+}
+
+// Distance to every active airstrip with known coordinates, sorted ascending
+fn airstrip_distances(latitude: f64, longitude: f64) -> Vec<AirstripDistance> {
+    let mut results: Vec<AirstripDistance> = AIRSTRIPS.with(|airstrips| {
+        airstrips
+            .borrow()
+            .iter()
+            .filter(|(_, airstrip)| airstrip.archived_at.is_none())
+            .filter_map(|(_, airstrip)| {
+                let coordinates = airstrip.coordinates?;
+                Some(AirstripDistance {
+                    distance_km: coordinates.distance_km(latitude, longitude),
+                    airstrip,
+                })
+            })
+            .collect()
+    });
+    results.sort_by(|a, b| a.distance_km.total_cmp(&b.distance_km));
+    results
+}
+
+// Longest length usable for both take-off and landing; falls back to the
+// airstrip's headline runway_length when no runways are recorded
+fn usable_runway_length(airstrip: &Airstrip) -> u64 {
+    airstrip_runways(airstrip.id)
+        .iter()
+        .map(|runway| runway.declared_distances.tora.min(runway.declared_distances.lda))
+        .max()
+        .unwrap_or(airstrip.runway_length)
+}
+
+// Every runway at the airstrip
+fn airstrip_runways(airstrip_id: u64) -> Vec<Runway> {
+    let ids = RUNWAYS_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, 0, u64::MAX));
+    RUNWAYS.with(|runways| {
+        let runways = runways.borrow();
+        ids.iter().filter_map(|id| runways.get(id)).collect()
+    })
+}
+
+// Looks up an airstrip that can still take new records
+fn get_active_airstrip(airstrip_id: u64) -> Result<Airstrip, Message> {
+    match AIRSTRIPS.with(|airstrips| airstrips.borrow().get(&airstrip_id)) {
+        Some(airstrip) if airstrip.archived_at.is_some() => {
+            Err(Message::Error("Airstrip has been archived".to_string()))
+        }
+        Some(airstrip) => Ok(airstrip),
+        None => Err(Message::NotFound("Airstrip not found".to_string())),
+    }
+}
+
+// Describes every record that still points at the airstrip, e.g. "2 flights"
+fn airstrip_references(airstrip_id: u64) -> Vec<String> {
+    let count = |index: &'static std::thread::LocalKey<RefCell<Index>>| {
+        index.with(|index| index.borrow().range(IndexKey::span(airstrip_id, 0, u64::MAX)).count())
+    };
+    let flights = count(&FLIGHTS_BY_AIRSTRIP);
+    let maintenance = count(&MAINTENANCE_BY_AIRSTRIP);
+    let fuel = count(&FUEL_BY_AIRSTRIP);
+    let revenues = count(&REVENUE_BY_AIRSTRIP);
+    let expenses = count(&EXPENSES_BY_AIRSTRIP);
+    let protocols = count(&PROTOCOLS_BY_AIRSTRIP);
+    let runways = count(&RUNWAYS_BY_AIRSTRIP);
+    let stands = count(&STANDS_BY_AIRSTRIP);
+    let based_aircraft = count(&AIRCRAFT_BY_BASE);
+
+    [
+        (flights, "flights"),
+        (maintenance, "maintenance schedules"),
+        (fuel, "fuel inventory records"),
+        (revenues, "revenue records"),
+        (expenses, "expense records"),
+        (protocols, "emergency protocols"),
+        (runways, "runways"),
+        (stands, "stands"),
+        (based_aircraft, "based aircraft"),
+    ]
+    .iter()
+    .filter(|(count, _)| *count > 0)
+    .map(|(count, kind)| format!("{} {}", count, kind))
+    .collect()
+}
+
+// Add Runway
+#[ic_cdk::update]
+fn add_runway(payload: AddRunwayPayload) -> Result<Runway, Message> {
+    authorize(|role| role.manages(payload.airstrip_id))?;
+
+    if payload.designator.is_empty() || payload.width == 0 {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Designator", &payload.designator, MAX_NAME_LEN)?;
+    if payload.heading >= 360 {
+        return Err(Message::InvalidPayload("Heading must be between 0 and 359 degrees".to_string()));
+    }
+    payload.declared_distances.validate()?;
+
+    get_active_airstrip(payload.airstrip_id)?;
+
+    if runway_designator_taken(payload.airstrip_id, &payload.designator, None) {
+        return Err(Message::InvalidPayload("Runway designator already in use at this airstrip".to_string()));
+    }
+
+    let runway_id = next_id();
+
+    let runway = Runway {
+        id: runway_id,
+        airstrip_id: payload.airstrip_id,
+        designator: payload.designator,
+        heading: payload.heading,
+        surface: payload.surface,
+        width: payload.width,
+        lighting: payload.lighting,
+        declared_distances: payload.declared_distances,
+        created_at: time(),
+    };
+
+    RUNWAYS.with(|runways| {
+        runways.borrow_mut().insert(runway_id, runway.clone());
+    });
+    index_runway(&runway);
+
+    record_audit(AuditAction::Create, EntityKind::Runway, runway_id, None, Some(&runway));
+
+    Ok(runway)
+}
+
+// Get Runway
+#[ic_cdk::query]
+fn get_runway(runway_id: u64) -> Result<Runway, Message> {
+    match RUNWAYS.with(|runways| runways.borrow().get(&runway_id)) {
+        Some(runway) => Ok(runway),
+        None => Err(Message::NotFound("Runway not found".to_string())),
+    }
+}
+
+// List Runways for an airstrip
+#[ic_cdk::query]
+fn list_runways(airstrip_id: u64, page: PageRequest) -> Result<Page<Runway>, Message> {
+    RUNWAYS_BY_AIRSTRIP.with(|index| {
+        collect_page(&index.borrow(), IndexKey::span(airstrip_id, 0, u64::MAX), &page, |key, _| {
+            RUNWAYS.with(|runways| runways.borrow().get(&key.id))
+        })
+    })
+}
+
+// Update Runway
+#[ic_cdk::update]
+fn update_runway(runway_id: u64, payload: UpdateRunwayPayload) -> Result<Runway, Message> {
+    let mut runway = get_runway(runway_id)?;
+    let before = runway.clone();
+    authorize(|role| role.manages(runway.airstrip_id))?;
+    get_active_airstrip(runway.airstrip_id)?;
+
+    if let Some(designator) = payload.designator {
+        if designator.is_empty() {
+            return Err(Message::InvalidPayload("Designator cannot be empty".to_string()));
+        }
+        check_len("Designator", &designator, MAX_NAME_LEN)?;
+        if runway_designator_taken(runway.airstrip_id, &designator, Some(runway_id)) {
+            return Err(Message::InvalidPayload("Runway designator already in use at this airstrip".to_string()));
+        }
+        runway.designator = designator;
+    }
+    if let Some(heading) = payload.heading {
+        if heading >= 360 {
+            return Err(Message::InvalidPayload("Heading must be between 0 and 359 degrees".to_string()));
+        }
+        runway.heading = heading;
+    }
+    if let Some(width) = payload.width {
+        if width == 0 {
+            return Err(Message::InvalidPayload("Width must be greater than zero".to_string()));
+        }
+        runway.width = width;
+    }
+    if let Some(declared_distances) = payload.declared_distances {
+        declared_distances.validate()?;
+        runway.declared_distances = declared_distances;
+    }
+    if let Some(surface) = payload.surface {
+        runway.surface = surface;
+    }
+    if let Some(lighting) = payload.lighting {
+        runway.lighting = lighting;
+    }
+
+    RUNWAYS.with(|runways| {
+        runways.borrow_mut().insert(runway_id, runway.clone());
+    });
+
+    record_audit(AuditAction::Update, EntityKind::Runway, runway_id, Some(&before), Some(&runway));
+
+    Ok(runway)
+}
+
+// Remove Runway (refused while flights still reference it)
+#[ic_cdk::update]
+fn remove_runway(runway_id: u64) -> Result<Runway, Message> {
+    let runway = get_runway(runway_id)?;
+    authorize(|role| role.manages(runway.airstrip_id))?;
+
+    let in_use = FLIGHTS_BY_RUNWAY.with(|index| {
+        index.borrow().range(IndexKey::span(runway_id, 0, u64::MAX)).next().is_some()
+    });
+    if in_use {
+        return Err(Message::Error("Runway is still referenced by flights".to_string()));
+    }
+
+    RUNWAYS.with(|runways| {
+        runways.borrow_mut().remove(&runway_id);
+    });
+    unindex_runway(&runway);
+
+    record_audit(AuditAction::Delete, EntityKind::Runway, runway_id, Some(&runway), None);
+
+    Ok(runway)
+}
+
+fn runway_designator_taken(airstrip_id: u64, designator: &str, except: Option<u64>) -> bool {
+    airstrip_runways(airstrip_id)
+        .iter()
+        .any(|runway| runway.designator.eq_ignore_ascii_case(designator) && Some(runway.id) != except)
+}
+
+// Schedule Flight
+#[ic_cdk::update]
+fn schedule_flight(payload: ScheduleFlightPayload) -> Result<FlightScheduled, Message> {
+    authorize(|role| *role == Role::Dispatcher || role.manages(payload.airstrip_id))?;
+
+    if payload.flight_number.is_empty() || payload.destination.is_empty() {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Flight number", &payload.flight_number, MAX_NAME_LEN)?;
+    check_len("Destination", &payload.destination, MAX_NAME_LEN)?;
+    if payload.arrival_time <= payload.departure_time {
+        return Err(Message::InvalidPayload("Arrival time must be after departure time".to_string()));
+    }
+    if payload.ground_time_minutes > MAX_GROUND_TIME_MINUTES {
+        return Err(Message::InvalidPayload(format!(
+            "Ground time cannot exceed {} minutes",
+            MAX_GROUND_TIME_MINUTES
+        )));
+    }
+
+    let airstrip = get_active_airstrip(payload.airstrip_id)?;
+    let aircraft = get_aircraft(payload.aircraft_id)?;
+    let flight_minutes = (payload.arrival_time - payload.departure_time) / NANOS_PER_MINUTE;
+    aircraft.check_airworthy(payload.departure_time, flight_minutes)?;
+
+    let runway = match payload.runway_id {
+        Some(runway_id) => match RUNWAYS.with(|runways| runways.borrow().get(&runway_id)) {
+            Some(runway) if runway.airstrip_id == payload.airstrip_id => Some(runway),
+            Some(_) => return Err(Message::InvalidPayload("Runway belongs to a different airstrip".to_string())),
+            None => return Err(Message::NotFound("Runway not found".to_string())),
+        },
+        None => None,
+    };
+
+    // Declared distances of the chosen runway win over the airstrip's headline length
+    let (takeoff_available, landing_available) = match &runway {
+        Some(runway) => (runway.declared_distances.toda, runway.declared_distances.lda),
+        None => (airstrip.runway_length, airstrip.runway_length),
+    };
+    if aircraft.takeoff_distance_required > takeoff_available {
+        return Err(Message::Error(format!(
+            "Aircraft {} needs {} m for take-off but only {} m is available",
+            aircraft.registration, aircraft.takeoff_distance_required, takeoff_available
+        )));
+    }
+    if aircraft.landing_distance_required > landing_available {
+        return Err(Message::Error(format!(
+            "Aircraft {} needs {} m for landing but only {} m is available",
+            aircraft.registration, aircraft.landing_distance_required, landing_available
+        )));
+    }
+
+    let flight_id = next_id();
+
+    let mut flight = Flight {
+        id: flight_id,
+        airstrip_id: payload.airstrip_id,
+        flight_number: payload.flight_number,
+        destination: payload.destination,
+        departure_time: payload.departure_time,
+        arrival_time: payload.arrival_time,
+        aircraft_id: payload.aircraft_id,
+        runway_id: payload.runway_id,
+        movement: payload.movement,
+        ground_time_minutes: payload.ground_time_minutes,
+        slot_id: None,
+        stand_assignment_id: None,
+        status: FlightStatus::Scheduled,
+        status_history: vec![FlightStatusChange {
+            status: FlightStatus::Scheduled,
+            changed_at: time(),
+        }],
+        archived_at: None,
+        category: payload.category,
+    };
+
+    check_capacity(&airstrip, &flight)?;
+
+    let (ground_start, ground_end) = flight.ground_window();
+    let stand = if ground_start < ground_end && airstrip_has_stands(airstrip.id) {
+        match free_stand(airstrip.id, aircraft.size_class(), ground_start, ground_end, None) {
+            Some(stand) => Some(stand),
+            None => return Err(Message::Error("No suitable parking stand is free for the ground time".to_string())),
+        }
+    } else {
+        None
+    };
+
+    let warnings = flight_maintenance_conflicts(&flight, &aircraft, stand.as_ref().map(|stand| stand.id));
+    if let Some(conflict) = warnings.iter().find(|conflict| conflict.blocking) {
+        return Err(Message::Error(conflict.detail.clone()));
+    }
+
+    flight.slot_id = Some(reserve_slot(&flight));
+    if let Some(stand) = stand {
+        flight.stand_assignment_id = Some(create_stand_assignment(&flight, stand.id, false).id);
+    }
+
+    FLIGHTS.with(|flights| {
+        flights.borrow_mut().insert(flight_id, flight.clone());
+    });
+    index_flight(&flight);
+
+    record_audit(AuditAction::Create, EntityKind::Flight, flight_id, None, Some(&flight));
+
+    Ok(FlightScheduled { flight, warnings })
+}
+
+// Rejects a flight that would exceed the hourly movement limit or the stand count
+fn check_capacity(airstrip: &Airstrip, flight: &Flight) -> Result<(), Message> {
+    let slot_start = flight.movement_time() / NANOS_PER_HOUR * NANOS_PER_HOUR;
+
+    if airstrip.movements_per_hour > 0 {
+        let ids = SLOTS_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip.id, slot_start, slot_start));
+        let booked = SLOT_RESERVATIONS.with(|slots| {
+            let slots = slots.borrow();
+            ids.iter()
+                .filter_map(|id| slots.get(id))
+                .filter(|slot| slot.flight_id != flight.id && slot.released_at.is_none())
+                .count() as u64
+        });
+        if booked >= airstrip.movements_per_hour {
+            return Err(Message::Error(format!(
+                "All {} movement slots in the hour starting at {} are taken",
+                airstrip.movements_per_hour, slot_start
+            )));
+        }
+    }
+
+    // Airstrips with named stands are checked stand by stand instead
+    let (start, end) = flight.ground_window();
+    if start < end
+        && !airstrip_has_stands(airstrip.id)
+        && peak_occupancy(airstrip.id, start, end, Some(flight.id)) >= airstrip.capacity
+    {
+        return Err(Message::Error("No parking stand is free for the whole ground time".to_string()));
+    }
+
+    Ok(())
+}
+
+fn reserve_slot(flight: &Flight) -> u64 {
+    let slot_id = next_id();
+
+    let movement_time = flight.movement_time();
+    let slot = SlotReservation {
+        id: slot_id,
+        airstrip_id: flight.airstrip_id,
+        flight_id: flight.id,
+        movement: flight.movement,
+        slot_start: movement_time / NANOS_PER_HOUR * NANOS_PER_HOUR,
+        movement_time,
+        created_at: time(),
+        released_at: None,
+    };
+
+    SLOT_RESERVATIONS.with(|slots| {
+        slots.borrow_mut().insert(slot_id, slot.clone());
+    });
+    index_slot(&slot);
+
+    slot_id
+}
+
+// Releases the slot, returning whether it was still held
+fn release_slot(slot_id: u64) -> bool {
+    SLOT_RESERVATIONS.with(|slots| {
+        let mut slots = slots.borrow_mut();
+        match slots.get(&slot_id) {
+            Some(mut slot) if slot.released_at.is_none() => {
+                slot.released_at = Some(time());
+                slots.insert(slot_id, slot);
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+// Ground windows of the airstrip's live flights that overlap [start, end)
+fn ground_windows(airstrip_id: u64, start: u64, end: u64, exclude_flight: Option<u64>) -> Vec<(u64, u64, u64)> {
+    // A ground window lies within MAX_GROUND_TIME of its flight's movement time
+    let ids = FLIGHTS_BY_AIRSTRIP.with(|index| {
+        index_ids(
+            &index.borrow(),
+            airstrip_id,
+            start.saturating_sub(MAX_GROUND_TIME),
+            end.saturating_add(MAX_GROUND_TIME),
+        )
+    });
+    FLIGHTS.with(|flights| {
+        let flights = flights.borrow();
+        ids.into_iter()
+            .filter(|id| Some(*id) != exclude_flight)
+            .filter_map(|id| flights.get(&id))
+            .filter(|flight| flight.occupies_capacity())
+            .filter_map(|flight| {
+                let (window_start, window_end) = flight.ground_window();
+                (window_start < end && window_end > start && window_start < window_end)
+                    .then_some((flight.id, window_start, window_end))
+            })
+            .collect()
+    })
+}
+
+// Highest number of aircraft on the ground at the same time within [start, end)
+fn peak_occupancy(airstrip_id: u64, start: u64, end: u64, exclude_flight: Option<u64>) -> u64 {
+    let mut events: Vec<(u64, i64)> = Vec::new();
+    for (_, window_start, window_end) in ground_windows(airstrip_id, start, end, exclude_flight) {
+        events.push((window_start.max(start), 1));
+        events.push((window_end.min(end), -1));
+    }
+    // Departures sort before arrivals at the same instant, so windows are half-open
+    events.sort();
+
+    let mut current: i64 = 0;
+    let mut peak: i64 = 0;
+    for (_, delta) in events {
+        current += delta;
+        peak = peak.max(current);
+    }
+    peak as u64
+}
+
+// List Flights
+#[ic_cdk::query]
+fn list_flights(filter: FlightFilter, page: PageRequest) -> Result<Page<Flight>, Message> {
+    let start = filter.start_time.unwrap_or(0);
+    let end = filter.end_time.unwrap_or(u64::MAX);
+    let keep = |flight: &Flight| {
+        (filter.include_archived || flight.archived_at.is_none())
+            && filter.status.is_none_or(|status| flight.status == status)
+            && (start..=end).contains(&flight.movement_time())
+    };
+
+    FLIGHTS.with(|flights| {
+        let flights = flights.borrow();
+        match filter.airstrip_id {
+            Some(airstrip_id) => FLIGHTS_BY_AIRSTRIP.with(|index| {
+                collect_page(&index.borrow(), IndexKey::span(airstrip_id, start, end), &page, |key, _| {
+                    flights.get(&key.id).filter(keep)
+                })
+            }),
+            None => collect_page(&flights, 0..=u64::MAX, &page, |_, flight| Some(flight).filter(keep)),
+        }
+    })
+}
+
+// Update Flight Status
+#[ic_cdk::update]
+fn update_flight_status(flight_id: u64, status: FlightStatus) -> Result<Flight, Message> {
+    change_flight_status(flight_id, status).map(|(flight, _)| flight)
+}
+
+// Applies a status change and what follows from it, returning the records a
+// cancellation released
+fn change_flight_status(flight_id: u64, status: FlightStatus) -> Result<(Flight, Vec<AffectedRecord>), Message> {
+    let mut flight = match FLIGHTS.with(|flights| flights.borrow().get(&flight_id)) {
+        Some(flight) => flight,
+        None => return Err(Message::NotFound("Flight not found".to_string())),
+    };
+    authorize(|role| *role == Role::Dispatcher || role.manages(flight.airstrip_id))?;
+    let before = flight.clone();
+
+    if flight.archived_at.is_some() {
+        return Err(Message::Error("Flight has been archived".to_string()));
+    }
+
+    if !flight.status.can_transition_to(status) {
+        return Err(Message::InvalidPayload(format!(
+            "Cannot change flight status from {:?} to {:?}",
+            flight.status, status
+        )));
+    }
+
+    flight.status = status;
+    flight.status_history.push(FlightStatusChange {
+        status,
+        changed_at: time(),
+    });
+
+    FLIGHTS.with(|flights| {
+        flights.borrow_mut().insert(flight_id, flight.clone());
+    });
+
+    record_audit(AuditAction::Update, EntityKind::Flight, flight_id, Some(&before), Some(&flight));
+
+    let affected = if status == FlightStatus::Cancelled {
+        release_flight(&flight)
+    } else {
+        Vec::new()
+    };
+
+    // Completing the flight completes its crew's schedules and credits their logbooks
+    // and the aircraft's hours and cycles
+    if status == FlightStatus::Completed {
+        log_aircraft_usage(&flight);
+        let schedule_ids = SCHEDULES_BY_FLIGHT.with(|index| index_ids(&index.borrow(), flight_id, 0, u64::MAX));
+        for schedule_id in schedule_ids {
+            if let Some(schedule) = PILOT_SCHEDULES.with(|schedules| schedules.borrow().get(&schedule_id)) {
+                if schedule.status == "scheduled" {
+                    complete_schedule(schedule, &flight);
+                }
+            }
+        }
+    }
+
+    Ok((flight, affected))
+}
+
+// Gives up what a cancelled flight held: its slot, its stand and its crew schedules
+fn release_flight(flight: &Flight) -> Vec<AffectedRecord> {
+    let mut affected = Vec::new();
+    if let Some(slot_id) = flight.slot_id {
+        if release_slot(slot_id) {
+            affected.push(AffectedRecord {
+                entity: EntityKind::SlotReservation,
+                id: slot_id,
+                effect: CascadeEffect::Released,
+            });
+        }
+    }
+    if let Some(assignment_id) = flight.stand_assignment_id {
+        if release_stand_assignment(assignment_id) {
+            affected.push(AffectedRecord {
+                entity: EntityKind::StandAssignment,
+                id: assignment_id,
+                effect: CascadeEffect::Released,
+            });
+        }
+    }
+    for schedule in flight_schedules(flight.id) {
+        if schedule.status == "scheduled" {
+            set_schedule_status(schedule.clone(), "cancelled");
+            affected.push(AffectedRecord {
+                entity: EntityKind::PilotSchedule,
+                id: schedule.id,
+                effect: CascadeEffect::Cancelled,
+            });
+        }
+    }
+    affected
+}
+
+// Cancel a flight, releasing its slot, stand and crew
+#[ic_cdk::update]
+fn cancel_flight(flight_id: u64) -> Result<CascadeReport, Message> {
+    let (_, released) = change_flight_status(flight_id, FlightStatus::Cancelled)?;
+
+    let mut affected = vec![AffectedRecord {
+        entity: EntityKind::Flight,
+        id: flight_id,
+        effect: CascadeEffect::Cancelled,
+    }];
+    affected.extend(released);
+    Ok(CascadeReport {
+        affected,
+        warnings: Vec::new(),
+    })
+}
+
+// Move a flight that has not departed. Capacity, the stand and the crew's duties
+// are checked again for the new times before anything changes. Duties shift with
+// the flight; if any would no longer pass the crew checks the move is refused.
+#[ic_cdk::update]
+fn reschedule_flight(flight_id: u64, payload: RescheduleFlightPayload) -> Result<CascadeReport, Message> {
+    let flight = match FLIGHTS.with(|flights| flights.borrow().get(&flight_id)) {
+        Some(flight) => flight,
+        None => return Err(Message::NotFound("Flight not found".to_string())),
+    };
+    authorize(|role| *role == Role::Dispatcher || role.manages(flight.airstrip_id))?;
+
+    if !flight.awaiting_departure() {
+        return Err(Message::Error("Only flights that have not departed can be moved".to_string()));
+    }
+    if payload.arrival_time <= payload.departure_time {
+        return Err(Message::InvalidPayload("Arrival time must be after departure time".to_string()));
+    }
+
+    let airstrip = get_active_airstrip(flight.airstrip_id)?;
+    let aircraft = get_aircraft(flight.aircraft_id)?;
+    let flight_minutes = (payload.arrival_time - payload.departure_time) / NANOS_PER_MINUTE;
+    aircraft.check_airworthy(payload.departure_time, flight_minutes)?;
+    let mut moved = flight.clone();
+    moved.departure_time = payload.departure_time;
+    moved.arrival_time = payload.arrival_time;
+
+    check_capacity(&airstrip, &moved)?;
+
+    // Keep the current stand when it is still free, otherwise find another
+    let (ground_start, ground_end) = moved.ground_window();
+    let stand_id = if ground_start < ground_end && airstrip_has_stands(airstrip.id) {
+        let current = moved
+            .stand_assignment_id
+            .and_then(|id| STAND_ASSIGNMENTS.with(|assignments| assignments.borrow().get(&id)));
+        match current {
+            Some(assignment)
+                if stand_conflict(assignment.stand_id, ground_start, ground_end, Some(flight_id)).is_none()
+                    && !closed_stands(airstrip.id, ground_start, ground_end).contains(&assignment.stand_id) =>
+            {
+                Some((assignment.stand_id, assignment.assigned_manually))
+            }
+            _ => match free_stand(airstrip.id, aircraft.size_class(), ground_start, ground_end, Some(flight_id)) {
+                Some(stand) => Some((stand.id, false)),
+                None => {
+                    return Err(Message::Error("No suitable parking stand is free for the new ground time".to_string()))
+                }
+            },
+        }
+    } else {
+        None
+    };
+
+    let warnings = flight_maintenance_conflicts(&moved, &aircraft, stand_id.map(|(stand_id, _)| stand_id));
+    if let Some(conflict) = warnings.iter().find(|conflict| conflict.blocking) {
+        return Err(Message::Error(conflict.detail.clone()));
+    }
+
+    // Duties keep their report and release margins around the flight
+    let mut crew_moves = Vec::new();
+    let mut misfits = Vec::new();
+    for schedule in flight_schedules(flight_id) {
+        if schedule.status != "scheduled" {
+            continue;
+        }
+        let duty = (
+            shift(schedule.start_time, flight.departure_time, moved.departure_time),
+            shift(schedule.end_time, flight.arrival_time, moved.arrival_time),
+        );
+        match check_schedule_move(&schedule, &moved, &aircraft, duty) {
+            Ok(()) => crew_moves.push((schedule, duty)),
+            Err(_) => misfits.push(schedule.id.to_string()),
+        }
+    }
+    if !misfits.is_empty() {
+        return Err(Message::Error(format!(
+            "Pilot schedules {} would no longer fit the new times; move or cancel them first",
+            misfits.join(", ")
+        )));
+    }
+
+    let mut affected = vec![AffectedRecord {
+        entity: EntityKind::Flight,
+        id: flight_id,
+        effect: CascadeEffect::Moved {
+            start_time: moved.departure_time,
+            end_time: moved.arrival_time,
+        },
+    }];
+
+    if let Some(slot_id) = flight.slot_id {
+        if release_slot(slot_id) {
+            affected.push(AffectedRecord {
+                entity: EntityKind::SlotReservation,
+                id: slot_id,
+                effect: CascadeEffect::Released,
+            });
+        }
+    }
+    let slot_id = reserve_slot(&moved);
+    moved.slot_id = Some(slot_id);
+    affected.push(AffectedRecord {
+        entity: EntityKind::SlotReservation,
+        id: slot_id,
+        effect: CascadeEffect::Created,
+    });
+
+    if let Some(assignment_id) = flight.stand_assignment_id {
+        if release_stand_assignment(assignment_id) {
+            affected.push(AffectedRecord {
+                entity: EntityKind::StandAssignment,
+                id: assignment_id,
+                effect: CascadeEffect::Released,
+            });
+        }
+    }
+    moved.stand_assignment_id = stand_id.map(|(stand_id, assigned_manually)| {
+        let assignment = create_stand_assignment(&moved, stand_id, assigned_manually);
+        affected.push(AffectedRecord {
+            entity: EntityKind::StandAssignment,
+            id: assignment.id,
+            effect: CascadeEffect::Created,
+        });
+        assignment.id
+    });
+
+    unindex_flight(&flight);
+    FLIGHTS.with(|flights| {
+        flights.borrow_mut().insert(flight_id, moved.clone());
+    });
+    index_flight(&moved);
+
+    record_audit(AuditAction::Update, EntityKind::Flight, flight_id, Some(&flight), Some(&moved));
+
+    for (schedule, duty) in crew_moves {
+        let schedule = store_schedule_move(&schedule, duty);
+        affected.push(AffectedRecord {
+            entity: EntityKind::PilotSchedule,
+            id: schedule.id,
+            effect: CascadeEffect::Moved {
+                start_time: schedule.start_time,
+                end_time: schedule.end_time,
+            },
+        });
+    }
+
+    Ok(CascadeReport { affected, warnings })
+}
+
+// Moves `timestamp` by the distance from `from` to `to`
+fn shift(timestamp: u64, from: u64, to: u64) -> u64 {
+    if to >= from {
+        timestamp.saturating_add(to - from)
+    } else {
+        timestamp.saturating_sub(from - to)
+    }
+}
+
+// Add Stand
+#[ic_cdk::update]
+fn add_stand(payload: AddStandPayload) -> Result<Stand, Message> {
+    authorize(|role| role.manages(payload.airstrip_id))?;
+
+    if payload.name.is_empty() {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Name", &payload.name, MAX_NAME_LEN)?;
+
+    get_active_airstrip(payload.airstrip_id)?;
+
+    if stand_name_taken(payload.airstrip_id, &payload.name, None) {
+        return Err(Message::InvalidPayload("Stand name already in use at this airstrip".to_string()));
+    }
+
+    let stand_id = next_id();
+
+    let stand = Stand {
+        id: stand_id,
+        airstrip_id: payload.airstrip_id,
+        name: payload.name,
+        size_class: payload.size_class,
+        has_fuel: payload.has_fuel,
+        has_power: payload.has_power,
+        created_at: time(),
+    };
+
+    STANDS.with(|stands| {
+        stands.borrow_mut().insert(stand_id, stand.clone());
+    });
+    index_stand(&stand);
+
+    record_audit(AuditAction::Create, EntityKind::Stand, stand_id, None, Some(&stand));
+
+    Ok(stand)
+}
+
+// Get Stand
+#[ic_cdk::query]
+fn get_stand(stand_id: u64) -> Result<Stand, Message> {
+    match STANDS.with(|stands| stands.borrow().get(&stand_id)) {
+        Some(stand) => Ok(stand),
+        None => Err(Message::NotFound("Stand not found".to_string())),
+    }
+}
+
+// List Stands for an airstrip
+#[ic_cdk::query]
+fn list_stands(airstrip_id: u64, page: PageRequest) -> Result<Page<Stand>, Message> {
+    STANDS_BY_AIRSTRIP.with(|index| {
+        collect_page(&index.borrow(), IndexKey::span(airstrip_id, 0, u64::MAX), &page, |key, _| {
+            STANDS.with(|stands| stands.borrow().get(&key.id))
+        })
+    })
+}
+
+// Every stand at the airstrip
+fn airstrip_stands(airstrip_id: u64) -> Vec<Stand> {
+    let ids = STANDS_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, 0, u64::MAX));
+    STANDS.with(|stands| {
+        let stands = stands.borrow();
+        ids.iter().filter_map(|id| stands.get(id)).collect()
+    })
+}
+
+// Update Stand
+#[ic_cdk::update]
+fn update_stand(stand_id: u64, payload: UpdateStandPayload) -> Result<Stand, Message> {
+    let mut stand = get_stand(stand_id)?;
+    let before = stand.clone();
+    authorize(|role| role.manages(stand.airstrip_id))?;
+    get_active_airstrip(stand.airstrip_id)?;
+
+    if let Some(name) = payload.name {
+        if name.is_empty() {
+            return Err(Message::InvalidPayload("Name cannot be empty".to_string()));
+        }
+        check_len("Name", &name, MAX_NAME_LEN)?;
+        if stand_name_taken(stand.airstrip_id, &name, Some(stand_id)) {
+            return Err(Message::InvalidPayload("Stand name already in use at this airstrip".to_string()));
+        }
+        stand.name = name;
+    }
+    if let Some(size_class) = payload.size_class {
+        stand.size_class = size_class;
+    }
+    if let Some(has_fuel) = payload.has_fuel {
+        stand.has_fuel = has_fuel;
+    }
+    if let Some(has_power) = payload.has_power {
+        stand.has_power = has_power;
+    }
+
+    STANDS.with(|stands| {
+        stands.borrow_mut().insert(stand_id, stand.clone());
+    });
+
+    record_audit(AuditAction::Update, EntityKind::Stand, stand_id, Some(&before), Some(&stand));
+
+    Ok(stand)
+}
+
+// Remove Stand (refused while it still has live assignments)
+#[ic_cdk::update]
+fn remove_stand(stand_id: u64) -> Result<Stand, Message> {
+    let stand = get_stand(stand_id)?;
+    authorize(|role| role.manages(stand.airstrip_id))?;
+
+    if !stand_assignments(stand_id, time(), u64::MAX).is_empty() {
+        return Err(Message::Error("Stand still has upcoming assignments".to_string()));
+    }
+
+    STANDS.with(|stands| {
+        stands.borrow_mut().remove(&stand_id);
+    });
+    unindex_stand(&stand);
+
+    record_audit(AuditAction::Delete, EntityKind::Stand, stand_id, Some(&stand), None);
+
+    Ok(stand)
+}
+
+// Assign Stand by hand, replacing any existing assignment for the flight
+#[ic_cdk::update]
+fn assign_stand(flight_id: u64, stand_id: u64) -> Result<StandAssignment, Message> {
+    let mut flight = match FLIGHTS.with(|flights| flights.borrow().get(&flight_id)) {
+        Some(flight) => flight,
+        None => return Err(Message::NotFound("Flight not found".to_string())),
+    };
+    authorize(|role| *role == Role::Dispatcher || role.manages(flight.airstrip_id))?;
+    let before = flight.clone();
+    if !flight.occupies_capacity() {
+        return Err(Message::Error("Flight is cancelled or archived".to_string()));
+    }
+
+    let stand = get_stand(stand_id)?;
+    if stand.airstrip_id != flight.airstrip_id {
+        return Err(Message::InvalidPayload("Stand belongs to a different airstrip".to_string()));
+    }
+
+    let aircraft = get_aircraft(flight.aircraft_id)?;
+    if aircraft.size_class() > stand.size_class {
+        return Err(Message::Error(format!(
+            "Stand {} is too small for {} ({:?})",
+            stand.name,
+            aircraft.registration,
+            aircraft.size_class()
+        )));
+    }
+
+    let (start, end) = flight.ground_window();
+    if start >= end {
+        return Err(Message::InvalidPayload("Flight has no ground time at this airstrip".to_string()));
+    }
+    if let Some(conflict) = stand_conflict(stand_id, start, end, Some(flight_id)) {
+        return Err(Message::Error(format!(
+            "Stand {} is already held by flight {} over that time",
+            stand.name, conflict.flight_id
+        )));
+    }
+    if closed_stands(flight.airstrip_id, start, end).contains(&stand_id) {
+        return Err(Message::Error(format!("Stand {} is closed for maintenance over that time", stand.name)));
+    }
+
+    if let Some(previous) = flight.stand_assignment_id {
+        release_stand_assignment(previous);
+    }
+    let assignment = create_stand_assignment(&flight, stand_id, true);
+
+    flight.stand_assignment_id = Some(assignment.id);
+    FLIGHTS.with(|flights| {
+        flights.borrow_mut().insert(flight_id, flight.clone());
+    });
+
+    record_audit(AuditAction::Update, EntityKind::Flight, flight_id, Some(&before), Some(&flight));
+
+    Ok(assignment)
+}
+
+fn stand_name_taken(airstrip_id: u64, name: &str, except: Option<u64>) -> bool {
+    airstrip_stands(airstrip_id)
+        .iter()
+        .any(|stand| stand.name.eq_ignore_ascii_case(name) && Some(stand.id) != except)
+}
+
+fn airstrip_has_stands(airstrip_id: u64) -> bool {
+    STANDS_BY_AIRSTRIP.with(|index| index.borrow().range(IndexKey::span(airstrip_id, 0, u64::MAX)).next().is_some())
+}
+
+// Live assignments on the stand that overlap [start, end), earliest first
+fn stand_assignments(stand_id: u64, start: u64, end: u64) -> Vec<StandAssignment> {
+    // An assignment never lasts longer than MAX_GROUND_TIME
+    let ids = ASSIGNMENTS_BY_STAND.with(|index| {
+        index_ids(&index.borrow(), stand_id, start.saturating_sub(MAX_GROUND_TIME), end.saturating_sub(1))
+    });
+    STAND_ASSIGNMENTS.with(|assignments| {
+        let assignments = assignments.borrow();
+        ids.iter()
+            .filter_map(|id| assignments.get(id))
+            .filter(|a| a.released_at.is_none() && a.start_time < end && a.end_time > start)
+            .collect()
+    })
+}
+
+// First live assignment on the stand that overlaps [start, end)
+fn stand_conflict(stand_id: u64, start: u64, end: u64, exclude_flight: Option<u64>) -> Option<StandAssignment> {
+    stand_assignments(stand_id, start, end)
+        .into_iter()
+        .find(|a| Some(a.flight_id) != exclude_flight)
+}
+
+// Smallest free stand that can take the aircraft for the whole window
+fn free_stand(airstrip_id: u64, size_class: SizeClass, start: u64, end: u64, exclude_flight: Option<u64>) -> Option<Stand> {
+    let closed = closed_stands(airstrip_id, start, end);
+    let mut candidates: Vec<Stand> = airstrip_stands(airstrip_id)
+        .into_iter()
+        .filter(|stand| stand.size_class >= size_class && !closed.contains(&stand.id))
+        .collect();
+    candidates.sort_by_key(|stand| (stand.size_class, stand.id));
+    candidates
+        .into_iter()
+        .find(|stand| stand_conflict(stand.id, start, end, exclude_flight).is_none())
+}
+
+fn create_stand_assignment(flight: &Flight, stand_id: u64, assigned_manually: bool) -> StandAssignment {
+    let assignment_id = next_id();
+
+    let (start_time, end_time) = flight.ground_window();
+    let assignment = StandAssignment {
+        id: assignment_id,
+        stand_id,
+        airstrip_id: flight.airstrip_id,
+        flight_id: flight.id,
+        aircraft_id: flight.aircraft_id,
+        start_time,
+        end_time,
+        assigned_manually,
+        created_at: time(),
+        released_at: None,
+    };
+
+    STAND_ASSIGNMENTS.with(|assignments| {
+        assignments.borrow_mut().insert(assignment_id, assignment.clone());
+    });
+    index_stand_assignment(&assignment);
+
+    assignment
+}
+
+// Releases the stand assignment, returning whether it was still held
+fn release_stand_assignment(assignment_id: u64) -> bool {
+    STAND_ASSIGNMENTS.with(|assignments| {
+        let mut assignments = assignments.borrow_mut();
+        match assignments.get(&assignment_id) {
+            Some(mut assignment) if assignment.released_at.is_none() => {
+                assignment.released_at = Some(time());
+                assignments.insert(assignment_id, assignment);
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+// Register Aircraft
+#[ic_cdk::update]
+fn register_aircraft(payload: RegisterAircraftPayload) -> Result<Aircraft, Message> {
+    authorize(|role| *role == Role::Dispatcher)?;
+
+    if payload.registration.trim().is_empty() || payload.aircraft_type.is_empty() || payload.operator.is_empty() {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Registration", &payload.registration, MAX_NAME_LEN)?;
+    check_len("Aircraft type", &payload.aircraft_type, MAX_NAME_LEN)?;
+    check_len("Fuel type", &payload.fuel_type, MAX_NAME_LEN)?;
+    check_len("Operator", &payload.operator, MAX_NAME_LEN)?;
+    if payload.mtow == 0 || payload.takeoff_distance_required == 0 || payload.landing_distance_required == 0 {
+        return Err(Message::InvalidPayload("MTOW and required distances must be greater than zero".to_string()));
+    }
+    if let Some(airstrip_id) = payload.base_airstrip_id {
+        get_active_airstrip(airstrip_id)?;
+    }
+
+    let registration = payload.registration.trim().to_uppercase();
+    let registration_taken = AIRCRAFT.with(|aircraft| {
+        aircraft.borrow().iter().any(|(_, a)| a.registration == registration)
+    });
+    if registration_taken {
+        return Err(Message::InvalidPayload("Aircraft registration already exists".to_string()));
+    }
+
+    let aircraft_id = next_id();
+
+    let aircraft = Aircraft {
+        id: aircraft_id,
+        registration,
+        aircraft_type: payload.aircraft_type,
+        mtow: payload.mtow,
+        seat_count: payload.seat_count,
+        takeoff_distance_required: payload.takeoff_distance_required,
+        landing_distance_required: payload.landing_distance_required,
+        fuel_type: payload.fuel_type,
+        operator: payload.operator,
+        created_at: time(),
+        min_flight_crew: payload.min_flight_crew,
+        base_airstrip_id: payload.base_airstrip_id,
+        airframe_minutes: payload.airframe_minutes,
+        engine_minutes: payload.engine_minutes,
+        landing_cycles: payload.landing_cycles,
+        grounded: None,
+        inspections: Vec::new(),
+    };
+
+    AIRCRAFT.with(|registry| {
+        registry.borrow_mut().insert(aircraft_id, aircraft.clone());
+    });
+    index_aircraft(&aircraft);
+
+    record_audit(AuditAction::Create, EntityKind::Aircraft, aircraft_id, None, Some(&aircraft));
+
+    Ok(aircraft)
+}
+
+// Get Aircraft
+#[ic_cdk::query]
+fn get_aircraft(aircraft_id: u64) -> Result<Aircraft, Message> {
+    match AIRCRAFT.with(|aircraft| aircraft.borrow().get(&aircraft_id)) {
+        Some(aircraft) => Ok(aircraft),
+        None => Err(Message::NotFound("Aircraft not found".to_string())),
+    }
+}
+
+// List Aircraft
+#[ic_cdk::query]
+fn list_aircraft(page: PageRequest) -> Result<Page<Aircraft>, Message> {
+    AIRCRAFT.with(|aircraft| collect_page(&aircraft.borrow(), 0..=u64::MAX, &page, |_, a| Some(a)))
+}
+
+// Update Aircraft
+#[ic_cdk::update]
+fn update_aircraft(aircraft_id: u64, payload: UpdateAircraftPayload) -> Result<Aircraft, Message> {
+    authorize(|role| *role == Role::Dispatcher)?;
+    let mut aircraft = get_aircraft(aircraft_id)?;
+    let before = aircraft.clone();
+
+    if let Some(aircraft_type) = payload.aircraft_type {
+        if aircraft_type.is_empty() {
+            return Err(Message::InvalidPayload("Aircraft type cannot be empty".to_string()));
+        }
+        check_len("Aircraft type", &aircraft_type, MAX_NAME_LEN)?;
+        aircraft.aircraft_type = aircraft_type;
+    }
+    if let Some(operator) = payload.operator {
+        if operator.is_empty() {
+            return Err(Message::InvalidPayload("Operator cannot be empty".to_string()));
+        }
+        check_len("Operator", &operator, MAX_NAME_LEN)?;
+        aircraft.operator = operator;
+    }
+    if let Some(mtow) = payload.mtow {
+        if mtow == 0 {
+            return Err(Message::InvalidPayload("MTOW must be greater than zero".to_string()));
+        }
+        aircraft.mtow = mtow;
+    }
+    if let Some(distance) = payload.takeoff_distance_required {
+        if distance == 0 {
+            return Err(Message::InvalidPayload("Take-off distance must be greater than zero".to_string()));
+        }
+        aircraft.takeoff_distance_required = distance;
+    }
+    if let Some(distance) = payload.landing_distance_required {
+        if distance == 0 {
+            return Err(Message::InvalidPayload("Landing distance must be greater than zero".to_string()));
+        }
+        aircraft.landing_distance_required = distance;
+    }
+    if let Some(seat_count) = payload.seat_count {
+        aircraft.seat_count = seat_count;
+    }
+    if let Some(fuel_type) = payload.fuel_type {
+        check_len("Fuel type", &fuel_type, MAX_NAME_LEN)?;
+        aircraft.fuel_type = fuel_type;
+    }
+    if let Some(min_flight_crew) = payload.min_flight_crew {
+        aircraft.min_flight_crew = min_flight_crew;
+    }
+    if let Some(airstrip_id) = payload.base_airstrip_id {
+        get_active_airstrip(airstrip_id)?;
+        aircraft.base_airstrip_id = Some(airstrip_id);
+    }
+    if let Some(airframe_minutes) = payload.airframe_minutes {
+        aircraft.airframe_minutes = airframe_minutes;
+    }
+    if let Some(engine_minutes) = payload.engine_minutes {
+        aircraft.engine_minutes = engine_minutes;
+    }
+    if let Some(landing_cycles) = payload.landing_cycles {
+        aircraft.landing_cycles = landing_cycles;
+    }
+
+    AIRCRAFT.with(|registry| {
+        registry.borrow_mut().insert(aircraft_id, aircraft.clone());
+    });
+    unindex_aircraft(&before);
+    index_aircraft(&aircraft);
+
+    record_audit(AuditAction::Update, EntityKind::Aircraft, aircraft_id, Some(&before), Some(&aircraft));
+
+    Ok(aircraft)
+}
+
+// Loads an aircraft for a dispatcher, or for the manager of the airstrip it is based at
+fn get_managed_aircraft(aircraft_id: u64) -> Result<Aircraft, Message> {
+    let aircraft = get_aircraft(aircraft_id)?;
+    authorize(|role| {
+        *role == Role::Dispatcher || aircraft.base_airstrip_id.is_some_and(|airstrip_id| role.manages(airstrip_id))
+    })?;
+    Ok(aircraft)
+}
+
+fn store_aircraft(before: &Aircraft, aircraft: &Aircraft) {
+    AIRCRAFT.with(|registry| {
+        registry.borrow_mut().insert(aircraft.id, aircraft.clone());
+    });
+    record_audit(AuditAction::Update, EntityKind::Aircraft, aircraft.id, Some(before), Some(aircraft));
+}
+
+// Add Inspection
+#[ic_cdk::update]
+fn add_inspection(aircraft_id: u64, payload: AddInspectionPayload) -> Result<Aircraft, Message> {
+    let mut aircraft = get_managed_aircraft(aircraft_id)?;
+    let before = aircraft.clone();
+
+    if let InspectionKind::AirworthinessDirective { reference } = &payload.kind {
+        if reference.trim().is_empty() {
+            return Err(Message::InvalidPayload("Airworthiness directive reference is required".to_string()));
+        }
+        check_len("Directive reference", reference, MAX_NAME_LEN)?;
+    }
+    if aircraft.inspections.len() >= MAX_LIST_LEN {
+        return Err(Message::InvalidPayload(format!(
+            "Aircraft cannot have more than {} inspections",
+            MAX_LIST_LEN
+        )));
+    }
+
+    let mut inspection = Inspection {
+        kind: payload.kind,
+        interval_minutes: payload.interval_minutes,
+        interval_days: payload.interval_days,
+        interval_cycles: payload.interval_cycles,
+        ..Default::default()
+    };
+    if !inspection.repeats() {
+        match inspection.kind {
+            InspectionKind::HundredHour => inspection.interval_minutes = 100 * 60,
+            InspectionKind::Annual => inspection.interval_days = 365,
+            InspectionKind::AirworthinessDirective { .. } => {}
+        }
+    }
+
+    let now = time();
+    inspection.reset(now, aircraft.airframe_minutes, aircraft.landing_cycles);
+    inspection.last_done_at = None;
+    inspection.due_at = payload.due_at.or(inspection.due_at);
+    inspection.due_airframe_minutes = payload.due_airframe_minutes.or(inspection.due_airframe_minutes);
+    inspection.due_cycles = payload.due_cycles.or(inspection.due_cycles);
+    inspection.closed = false;
+    if inspection.due_at.is_none() && inspection.due_airframe_minutes.is_none() && inspection.due_cycles.is_none() {
+        return Err(Message::InvalidPayload(
+            "An inspection needs an interval or a due date, flight time or cycle count".to_string(),
+        ));
+    }
+
+    inspection.id = next_id();
+    aircraft.inspections.push(inspection);
+
+    store_aircraft(&before, &aircraft);
+    Ok(aircraft)
+}
+
+// Record Inspection: signs an inspection off and moves it to its next limits.
+// One-off inspections such as most directives are closed instead.
+#[ic_cdk::update]
+fn record_inspection(aircraft_id: u64, inspection_id: u64, performed_at: Option<u64>) -> Result<Aircraft, Message> {
+    let mut aircraft = get_managed_aircraft(aircraft_id)?;
+    let before = aircraft.clone();
+
+    let now = time();
+    let performed_at = performed_at.unwrap_or(now);
+    if performed_at > now {
+        return Err(Message::InvalidPayload("Inspection cannot be recorded in the future".to_string()));
+    }
+
+    let (airframe_minutes, landing_cycles) = (aircraft.airframe_minutes, aircraft.landing_cycles);
+    match aircraft.inspections.iter_mut().find(|inspection| inspection.id == inspection_id) {
+        Some(inspection) if inspection.closed => {
+            return Err(Message::Error("Inspection has already been closed".to_string()))
+        }
+        Some(inspection) => inspection.reset(performed_at, airframe_minutes, landing_cycles),
+        None => return Err(Message::NotFound("Inspection not found".to_string())),
+    }
+
+    store_aircraft(&before, &aircraft);
+    Ok(aircraft)
+}
+
+// Ground Aircraft
+#[ic_cdk::update]
+fn ground_aircraft(aircraft_id: u64, reason: String) -> Result<Aircraft, Message> {
+    let mut aircraft = get_managed_aircraft(aircraft_id)?;
+    let before = aircraft.clone();
+
+    if reason.trim().is_empty() {
+        return Err(Message::InvalidPayload("A reason is required to ground an aircraft".to_string()));
+    }
+    check_len("Reason", &reason, MAX_TEXT_LEN)?;
+    if aircraft.grounded.is_some() {
+        return Err(Message::Error("Aircraft is already grounded".to_string()));
+    }
+
+    aircraft.grounded = Some(Grounding { reason, since: time() });
+
+    store_aircraft(&before, &aircraft);
+    Ok(aircraft)
+}
+
+// Release Aircraft: returns a grounded aircraft to service
+#[ic_cdk::update]
+fn release_aircraft(aircraft_id: u64) -> Result<Aircraft, Message> {
+    let mut aircraft = get_managed_aircraft(aircraft_id)?;
+    let before = aircraft.clone();
+
+    if aircraft.grounded.is_none() {
+        return Err(Message::Error("Aircraft is not grounded".to_string()));
+    }
+    aircraft.grounded = None;
+
+    store_aircraft(&before, &aircraft);
+    Ok(aircraft)
+}
+
+// Get Aircraft Status: airworthiness and what is left on each open inspection
+#[ic_cdk::query]
+fn get_aircraft_status(aircraft_id: u64, as_of: Option<u64>) -> Result<AircraftStatus, Message> {
+    let aircraft = get_aircraft(aircraft_id)?;
+    Ok(aircraft_status(aircraft, as_of.unwrap_or_else(time)))
+}
+
+fn aircraft_status(aircraft: Aircraft, at: u64) -> AircraftStatus {
+    let inspections = aircraft
+        .inspections
+        .iter()
+        .filter(|inspection| !inspection.closed)
+        .map(|inspection| aircraft.inspection_status(inspection, at))
+        .collect();
+    AircraftStatus {
+        airworthy: aircraft.check_airworthy(at, 0).is_ok(),
+        aircraft,
+        inspections,
+    }
+}
+
+// Get Aircraft Due: aircraft based at an airstrip that are grounded, overdue,
+// or have an inspection falling due within the given days, flight hours or cycles
+#[ic_cdk::query]
+fn get_aircraft_due(filter: AircraftDueFilter) -> Result<Vec<AircraftStatus>, Message> {
+    get_airstrip(filter.airstrip_id)?;
+
+    let as_of = filter.as_of.unwrap_or_else(time);
+    let due_soon = |status: &InspectionStatus| {
+        status.overdue
+            || status.remaining_days.is_some_and(|days| days < filter.within_days)
+            || status
+                .remaining_minutes
+                .is_some_and(|minutes| minutes < filter.within_flight_hours.saturating_mul(60))
+            || status.remaining_cycles.is_some_and(|cycles| cycles < filter.within_cycles)
+    };
+
+    let ids = AIRCRAFT_BY_BASE.with(|index| index_ids(&index.borrow(), filter.airstrip_id, 0, u64::MAX));
+    Ok(AIRCRAFT.with(|aircraft| {
+        let aircraft = aircraft.borrow();
+        ids.iter()
+            .filter_map(|id| aircraft.get(id))
+            .map(|a| aircraft_status(a, as_of))
+            .filter(|status| !status.airworthy || status.inspections.iter().any(due_soon))
+            .collect()
+    }))
+}
+
+// Credits a completed leg to its aircraft: flight time to the airframe and
+// engine, and one landing cycle. A leg between two airstrips kept here has a
+// departure record at one and an arrival record at the other, so whichever
+// completes second finds the leg already credited.
+fn log_aircraft_usage(flight: &Flight) {
+    if leg_already_logged(flight) {
+        return;
+    }
+    if let Some(mut aircraft) = AIRCRAFT.with(|aircraft| aircraft.borrow().get(&flight.aircraft_id)) {
+        let before = aircraft.clone();
+        // Baseline records were never checked for arrival after departure
+        let flight_minutes = flight.arrival_time.saturating_sub(flight.departure_time) / NANOS_PER_MINUTE;
+        aircraft.airframe_minutes += flight_minutes;
+        aircraft.engine_minutes += flight_minutes;
+        aircraft.landing_cycles += 1;
+        store_aircraft(&before, &aircraft);
+    }
+}
+
+// Whether the other end's record of the same leg has already completed
+fn leg_already_logged(flight: &Flight) -> bool {
+    let ids = FLIGHTS_BY_AIRCRAFT.with(|index| {
+        index_ids(&index.borrow(), flight.aircraft_id, flight.departure_time, flight.departure_time)
+    });
+    FLIGHTS.with(|flights| {
+        let flights = flights.borrow();
+        ids.iter().filter(|id| **id != flight.id).filter_map(|id| flights.get(id)).any(|other| {
+            other.movement != flight.movement
+                && other.arrival_time == flight.arrival_time
+                && other.status == FlightStatus::Completed
+        })
+    })
+}
+
+// Register Pilot
+#[ic_cdk::update]
+fn register_pilot(payload: RegisterPilotPayload) -> Result<Pilot, Message> {
+    authorize(|role| *role == Role::Dispatcher)?;
+
+    if payload.name.is_empty() || payload.license_number.is_empty() {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Name", &payload.name, MAX_NAME_LEN)?;
+    check_len("License number", &payload.license_number, MAX_NAME_LEN)?;
+    check_len("Contact", &payload.contact, MAX_NAME_LEN)?;
+    check_len("Email", &payload.email, MAX_NAME_LEN)?;
+    check_list("Type ratings", &payload.type_ratings, MAX_NAME_LEN)?;
+    if payload.license_expiry == 0 || payload.medical_expiry == 0 {
+        return Err(Message::InvalidPayload("License and medical expiry dates are required".to_string()));
+    }
+
+    let pilot_id = next_id();
+
+    let pilot = Pilot {
+        id: pilot_id,
+        name: payload.name,
+        license_number: payload.license_number,
+        experience_years: payload.experience_years,
+        contact: payload.contact,
+        email: payload.email,
+        license_class: payload.license_class,
+        license_expiry: Some(payload.license_expiry),
+        medical_class: payload.medical_class,
+        medical_expiry: Some(payload.medical_expiry),
+        type_ratings: payload.type_ratings,
+        crew_roles: payload.crew_roles,
+    };
+
+    PILOTS.with(|pilots| {
+        pilots.borrow_mut().insert(pilot_id, pilot.clone());
+    });
+
+    record_audit(AuditAction::Create, EntityKind::Pilot, pilot_id, None, Some(&pilot));
+
+    Ok(pilot)
+}
+
+// Get Pilot
+#[ic_cdk::query]
+fn get_pilot(pilot_id: u64) -> Result<Pilot, Message> {
+    match PILOTS.with(|pilots| pilots.borrow().get(&pilot_id)) {
+        Some(pilot) => Ok(pilot),
+        None => Err(Message::NotFound("Pilot not found".to_string())),
+    }
+}
+
+// Update Pilot, including licence, medical and type-rating renewals
+#[ic_cdk::update]
+fn update_pilot(pilot_id: u64, payload: UpdatePilotPayload) -> Result<Pilot, Message> {
+    authorize(|role| *role == Role::Dispatcher)?;
+    let mut pilot = get_pilot(pilot_id)?;
+    let before = pilot.clone();
+
+    if let Some(name) = payload.name {
+        if name.is_empty() {
+            return Err(Message::InvalidPayload("Name cannot be empty".to_string()));
+        }
+        check_len("Name", &name, MAX_NAME_LEN)?;
+        pilot.name = name;
+    }
+    if let Some(license_number) = payload.license_number {
+        if license_number.is_empty() {
+            return Err(Message::InvalidPayload("License number cannot be empty".to_string()));
+        }
+        check_len("License number", &license_number, MAX_NAME_LEN)?;
+        pilot.license_number = license_number;
+    }
+    if let Some(experience_years) = payload.experience_years {
+        pilot.experience_years = experience_years;
+    }
+    if let Some(contact) = payload.contact {
+        check_len("Contact", &contact, MAX_NAME_LEN)?;
+        pilot.contact = contact;
+    }
+    if let Some(email) = payload.email {
+        check_len("Email", &email, MAX_NAME_LEN)?;
+        pilot.email = email;
+    }
+    if let Some(license_class) = payload.license_class {
+        pilot.license_class = license_class;
+    }
+    if let Some(expiry) = payload.license_expiry {
+        if expiry == 0 {
+            return Err(Message::InvalidPayload("License expiry cannot be zero".to_string()));
+        }
+        pilot.license_expiry = Some(expiry);
+    }
+    if let Some(medical_class) = payload.medical_class {
+        pilot.medical_class = medical_class;
+    }
+    if let Some(expiry) = payload.medical_expiry {
+        if expiry == 0 {
+            return Err(Message::InvalidPayload("Medical expiry cannot be zero".to_string()));
+        }
+        pilot.medical_expiry = Some(expiry);
+    }
+    if let Some(type_ratings) = payload.type_ratings {
+        check_list("Type ratings", &type_ratings, MAX_NAME_LEN)?;
+        pilot.type_ratings = type_ratings;
+    }
+    if let Some(crew_roles) = payload.crew_roles {
+        pilot.crew_roles = crew_roles;
+    }
+
+    PILOTS.with(|pilots| {
+        pilots.borrow_mut().insert(pilot_id, pilot.clone());
+    });
+
+    record_audit(AuditAction::Update, EntityKind::Pilot, pilot_id, Some(&before), Some(&pilot));
+
+    Ok(pilot)
+}
+
+// List Pilots
+#[ic_cdk::query]
+fn list_pilots(filter: PilotFilter, page: PageRequest) -> Result<Page<Pilot>, Message> {
+    let name = filter.name.map(|name| name.to_lowercase());
+    PILOTS.with(|pilots| {
+        collect_page(&pilots.borrow(), 0..=u64::MAX, &page, |_, pilot| {
+            let matches = name.as_ref().is_none_or(|name| pilot.name.to_lowercase().contains(name))
+                && filter.min_experience_years.is_none_or(|years| pilot.experience_years >= years);
+            matches.then_some(pilot)
+        })
+    })
+}
+
+// Schedule Maintenance
+#[ic_cdk::update]
+fn schedule_maintenance(payload: ScheduleMaintenancePayload) -> Result<MaintenanceScheduled, Message> {
+    authorize(|role| role.manages(payload.airstrip_id))?;
+
+    if payload.description.is_empty() {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Description", &payload.description, MAX_TEXT_LEN)?;
+
+    get_active_airstrip(payload.airstrip_id)?;
+    check_maintenance_window(payload.date, payload.end_time)?;
+    check_maintenance_impact(payload.airstrip_id, &payload.impact, payload.runway_id)?;
+    check_list("Technician", &payload.technicians, MAX_NAME_LEN)?;
+    check_list("Task", &payload.tasks, MAX_TEXT_LEN)?;
+
+    Ok(insert_maintenance(MaintenanceSchedule {
+        airstrip_id: payload.airstrip_id,
+        date: payload.date,
+        description: payload.description,
+        status: MaintenanceStatus::Scheduled,
+        archived_at: None,
+        end_time: payload.end_time,
+        impact: payload.impact,
+        runway_id: payload.runway_id,
+        technicians: payload.technicians,
+        tasks: payload
+            .tasks
+            .into_iter()
+            .map(|description| WorkTask { description, done: false })
+            .collect(),
+        ..Default::default()
+    }))
+}
+
+// Stores the maintenance under a new id and finds the flights it affects
+fn insert_maintenance(mut maintenance: MaintenanceSchedule) -> MaintenanceScheduled {
+    maintenance.id = next_id();
+
+    MAINTENANCE_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(maintenance.id, maintenance.clone());
+    });
+    index_maintenance(&maintenance);
+
+    record_audit(AuditAction::Create, EntityKind::MaintenanceSchedule, maintenance.id, None, Some(&maintenance));
+
+    let affected_flights = flights_affected_by(&maintenance);
+    MaintenanceScheduled {
+        maintenance,
+        affected_flights,
+    }
+}
+
+fn check_maintenance_window(start_time: u64, end_time: u64) -> Result<(), Message> {
+    if end_time <= start_time {
+        return Err(Message::InvalidPayload("Maintenance must end after it starts".to_string()));
+    }
+    if end_time - start_time > MAX_MAINTENANCE_WINDOW_DAYS * NANOS_PER_DAY {
+        return Err(Message::InvalidPayload(format!(
+            "A maintenance window cannot be longer than {} days",
+            MAX_MAINTENANCE_WINDOW_DAYS
+        )));
+    }
+    Ok(())
+}
+
+fn check_maintenance_impact(airstrip_id: u64, impact: &MaintenanceImpact, runway_id: Option<u64>) -> Result<(), Message> {
+    if let Some(runway_id) = runway_id {
+        match RUNWAYS.with(|runways| runways.borrow().get(&runway_id)) {
+            Some(runway) if runway.airstrip_id == airstrip_id => {}
+            Some(_) => return Err(Message::InvalidPayload("Runway belongs to a different airstrip".to_string())),
+            None => return Err(Message::NotFound("Runway not found".to_string())),
+        }
+    }
+
+    match impact {
+        MaintenanceImpact::ReducedLength { available_length: 0 } => Err(Message::InvalidPayload(
+            "A runway with no length left is a full closure".to_string(),
+        )),
+        MaintenanceImpact::PartialStands { stand_ids } => {
+            if stand_ids.is_empty() || stand_ids.len() > MAX_LIST_LEN {
+                return Err(Message::InvalidPayload(format!(
+                    "A partial stand closure must name between 1 and {} stands",
+                    MAX_LIST_LEN
+                )));
+            }
+            for stand_id in stand_ids {
+                if get_stand(*stand_id)?.airstrip_id != airstrip_id {
+                    return Err(Message::InvalidPayload("Stand belongs to a different airstrip".to_string()));
+                }
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+// Live maintenance at the airstrip whose window overlaps [start, end)
+fn maintenance_windows(airstrip_id: u64, start: u64, end: u64) -> Vec<MaintenanceSchedule> {
+    let longest = MAX_MAINTENANCE_WINDOW_DAYS * NANOS_PER_DAY;
+    let ids = MAINTENANCE_BY_AIRSTRIP.with(|index| {
+        index_ids(&index.borrow(), airstrip_id, start.saturating_sub(longest), end.saturating_sub(1))
+    });
+    MAINTENANCE_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        ids.iter()
+            .filter_map(|id| schedules.get(id))
+            .filter(|maintenance| {
+                let (window_start, window_end) = maintenance.window();
+                maintenance.is_live() && window_start < end && window_end > start
+            })
+            .collect()
+    })
+}
+
+// Stands closed by maintenance at any point within [start, end)
+fn closed_stands(airstrip_id: u64, start: u64, end: u64) -> Vec<u64> {
+    maintenance_windows(airstrip_id, start, end)
+        .into_iter()
+        .flat_map(|maintenance| match maintenance.impact {
+            MaintenanceImpact::PartialStands { stand_ids } => stand_ids,
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+// How the maintenance bears on the flight holding `stand_id`, if at all. Runway
+// work counts when it overlaps the movement's runway slot. A closure blocks the
+// flight when it falls on the flight's runway; when the flight has no runway
+// recorded, work on one runway blocks only if no other runway is long enough.
+fn maintenance_conflict(
+    maintenance: &MaintenanceSchedule,
+    flight: &Flight,
+    aircraft: &Aircraft,
+    stand_id: Option<u64>,
+) -> Option<MaintenanceConflict> {
+    let (start, end) = maintenance.window();
+    let (runway_start, runway_end) = flight.runway_window();
+    let during = start < runway_end && end > runway_start;
+    let runway_closed = maintenance.runway_id.is_none() || maintenance.runway_id == flight.runway_id;
+    let runway_unknown = flight.runway_id.is_none();
+    let required = match flight.movement {
+        MovementType::Arrival => aircraft.landing_distance_required,
+        MovementType::Departure => aircraft.takeoff_distance_required,
+    };
+
+    let (blocking, detail) = match &maintenance.impact {
+        MaintenanceImpact::FullClosure if during && runway_closed => {
+            (true, format!("Runway closed for maintenance: {}", maintenance.description))
+        }
+        MaintenanceImpact::FullClosure if during && runway_unknown => {
+            if other_runway_fits(flight, required, maintenance.runway_id) {
+                (false, format!("A runway is closed for maintenance: {}", maintenance.description))
+            } else {
+                (
+                    true,
+                    format!(
+                        "The only runway long enough for aircraft {} is closed for maintenance: {}",
+                        aircraft.registration, maintenance.description
+                    ),
+                )
+            }
+        }
+        MaintenanceImpact::ReducedLength { available_length } if during && (runway_closed || runway_unknown) => {
+            let too_short = required > *available_length;
+            (
+                too_short && (runway_closed || !other_runway_fits(flight, required, maintenance.runway_id)),
+                format!(
+                    "Runway shortened to {} m for maintenance; aircraft {} needs {} m",
+                    available_length, aircraft.registration, required
+                ),
+            )
+        }
+        MaintenanceImpact::PartialStands { stand_ids } => {
+            let (ground_start, ground_end) = flight.ground_window();
+            match stand_id {
+                Some(stand_id) if stand_ids.contains(&stand_id) && ground_start < end && ground_end > start => {
+                    (true, format!("Stand {} is closed for maintenance during the ground time", stand_id))
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    Some(MaintenanceConflict {
+        maintenance_id: maintenance.id,
+        flight_id: flight.id,
+        flight_number: flight.flight_number.clone(),
+        blocking,
+        detail,
+    })
+}
+
+// Whether a runway at the flight's airstrip other than `closed_runway` offers
+// the `required` distance for the flight's movement
+fn other_runway_fits(flight: &Flight, required: u64, closed_runway: Option<u64>) -> bool {
+    airstrip_runways(flight.airstrip_id)
+        .iter()
+        .filter(|runway| Some(runway.id) != closed_runway)
+        .any(|runway| {
+            let available = match flight.movement {
+                MovementType::Arrival => runway.declared_distances.lda,
+                MovementType::Departure => runway.declared_distances.toda,
+            };
+            available >= required
+        })
+}
+
+// Every maintenance conflict of the flight at its airstrip
+fn flight_maintenance_conflicts(flight: &Flight, aircraft: &Aircraft, stand_id: Option<u64>) -> Vec<MaintenanceConflict> {
+    let (runway_start, runway_end) = flight.runway_window();
+    let (ground_start, ground_end) = flight.ground_window();
+    let start = runway_start.min(ground_start);
+    let end = runway_end.max(ground_end);
+    maintenance_windows(flight.airstrip_id, start, end)
+        .iter()
+        .filter_map(|maintenance| maintenance_conflict(maintenance, flight, aircraft, stand_id))
+        .collect()
+}
+
+// Existing live flights the maintenance conflicts with
+fn flights_affected_by(maintenance: &MaintenanceSchedule) -> Vec<MaintenanceConflict> {
+    let (start, end) = maintenance.window();
+    let ids = FLIGHTS_BY_AIRSTRIP.with(|index| {
+        index_ids(
+            &index.borrow(),
+            maintenance.airstrip_id,
+            start.saturating_sub(MAX_GROUND_TIME),
+            end.saturating_add(MAX_GROUND_TIME),
+        )
+    });
+    ids.iter()
+        .filter_map(|id| FLIGHTS.with(|flights| flights.borrow().get(id)))
+        .filter(|flight| flight.occupies_capacity() && flight.status != FlightStatus::Completed)
+        .filter_map(|flight| {
+            let aircraft = AIRCRAFT.with(|aircraft| aircraft.borrow().get(&flight.aircraft_id)).unwrap_or_default();
+            let stand_id = flight
+                .stand_assignment_id
+                .and_then(|id| STAND_ASSIGNMENTS.with(|assignments| assignments.borrow().get(&id)))
+                .filter(|assignment| assignment.released_at.is_none())
+                .map(|assignment| assignment.stand_id);
+            maintenance_conflict(maintenance, &flight, &aircraft, stand_id)
+        })
+        .collect()
+}
+
+fn get_managed_maintenance(maintenance_id: u64) -> Result<(Principal, MaintenanceSchedule), Message> {
+    let maintenance = match MAINTENANCE_SCHEDULES.with(|schedules| schedules.borrow().get(&maintenance_id)) {
+        Some(maintenance) => maintenance,
+        None => return Err(Message::NotFound("Maintenance schedule not found".to_string())),
+    };
+    let caller = authorize(|role| role.manages(maintenance.airstrip_id))?;
+
+    if maintenance.archived_at.is_some() {
+        return Err(Message::Error("Maintenance schedule has been archived".to_string()));
+    }
+    Ok((caller, maintenance))
+}
+
+// Moves the work order to `status`, stamping the time it started or finished
+fn transition_maintenance(maintenance: &mut MaintenanceSchedule, status: MaintenanceStatus) -> Result<(), Message> {
+    if !maintenance.status.can_transition_to(status) {
+        return Err(Message::InvalidPayload(format!(
+            "Cannot change maintenance status from {:?} to {:?}",
+            maintenance.status, status
+        )));
+    }
+    maintenance.status = status;
+    match status {
+        MaintenanceStatus::InProgress => maintenance.started_at = Some(time()),
+        MaintenanceStatus::Completed => maintenance.completed_at = Some(time()),
+        MaintenanceStatus::Scheduled | MaintenanceStatus::Cancelled => {}
+    }
+    Ok(())
+}
+
+fn store_maintenance(before: &MaintenanceSchedule, maintenance: &MaintenanceSchedule) {
+    MAINTENANCE_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(maintenance.id, maintenance.clone());
+    });
+    record_audit(AuditAction::Update, EntityKind::MaintenanceSchedule, maintenance.id, Some(before), Some(maintenance));
+}
+
+// Start work on a maintenance task
+#[ic_cdk::update]
+fn start_maintenance(maintenance_id: u64) -> Result<MaintenanceSchedule, Message> {
+    let (_, before) = get_managed_maintenance(maintenance_id)?;
+    let mut maintenance = before.clone();
+    transition_maintenance(&mut maintenance, MaintenanceStatus::InProgress)?;
+    store_maintenance(&before, &maintenance);
+    Ok(maintenance)
+}
+
+// Replace the technicians, tasks, parts or labour on an open work order
+#[ic_cdk::update]
+fn update_work_order(maintenance_id: u64, payload: UpdateWorkOrderPayload) -> Result<MaintenanceSchedule, Message> {
+    let (_, before) = get_managed_maintenance(maintenance_id)?;
+    if !before.is_live() {
+        return Err(Message::Error(format!("Work order is already {:?}", before.status)));
+    }
+    let mut maintenance = before.clone();
+
+    if let Some(technicians) = payload.technicians {
+        check_list("Technician", &technicians, MAX_NAME_LEN)?;
+        maintenance.technicians = technicians;
+    }
+    if let Some(tasks) = payload.tasks {
+        if tasks.len() > MAX_LIST_LEN {
+            return Err(Message::InvalidPayload(format!("A work order cannot have more than {} tasks", MAX_LIST_LEN)));
+        }
+        for task in &tasks {
+            check_len("Task", &task.description, MAX_TEXT_LEN)?;
+        }
+        maintenance.tasks = tasks;
+    }
+    if let Some(parts) = payload.parts {
+        if parts.len() > MAX_LIST_LEN {
+            return Err(Message::InvalidPayload(format!("A work order cannot list more than {} parts", MAX_LIST_LEN)));
+        }
+        for part in &parts {
+            check_len("Part number", &part.part_number, MAX_NAME_LEN)?;
+            check_len("Part description", &part.description, MAX_NAME_LEN)?;
+            if !part.unit_cost.is_finite() || part.unit_cost < 0.0 {
+                return Err(Message::InvalidPayload("Part cost must be a non-negative amount".to_string()));
+            }
+        }
+        maintenance.parts = parts;
+    }
+    if let Some(labour) = payload.labour {
+        if labour.len() > MAX_LIST_LEN {
+            return Err(Message::InvalidPayload(format!(
+                "A work order cannot have more than {} labour entries",
+                MAX_LIST_LEN
+            )));
+        }
+        for entry in &labour {
+            check_len("Technician", &entry.technician, MAX_NAME_LEN)?;
+            let valid = |value: f64| value.is_finite() && value >= 0.0;
+            if !valid(entry.hours) || !valid(entry.hourly_rate) {
+                return Err(Message::InvalidPayload("Labour hours and rate must be non-negative".to_string()));
+            }
+        }
+        maintenance.labour = labour;
+    }
+
+    store_maintenance(&before, &maintenance);
+    Ok(maintenance)
+}
+
+// Complete a work order. The caller signs it off, and its parts and labour
+// cost is booked as a maintenance expense against the airstrip.
+#[ic_cdk::update]
+fn complete_maintenance(maintenance_id: u64, completion_notes: String) -> Result<MaintenanceSchedule, Message> {
+    let (caller, before) = get_managed_maintenance(maintenance_id)?;
+    check_len("Completion notes", &completion_notes, MAX_TEXT_LEN)?;
+    if before.technicians.is_empty() {
+        return Err(Message::InvalidPayload("Assign at least one technician before completing".to_string()));
+    }
+    if let Some(task) = before.tasks.iter().find(|task| !task.done) {
+        return Err(Message::Error(format!("Task not done: {}", task.description)));
+    }
+
+    let mut maintenance = before.clone();
+    transition_maintenance(&mut maintenance, MaintenanceStatus::Completed)?;
+    maintenance.completion_notes = completion_notes;
+    maintenance.signed_off_by = Some(caller);
+
+    let cost = maintenance.parts_cost() + maintenance.labour_cost();
+    if cost > 0.0 {
+        let expense = insert_expense(Expense {
+            airstrip_id: maintenance.airstrip_id,
+            category: "maintenance".to_string(),
+            amount: cost,
+            transaction_date: time(),
+            description: format!("Work order {}: {}", maintenance.id, maintenance.description),
+            maintenance_id: Some(maintenance.id),
+            ..Default::default()
+        });
+        maintenance.expense_id = Some(expense.id);
+    }
+
+    store_maintenance(&before, &maintenance);
+    Ok(maintenance)
+}
+
+// Cancel a maintenance task that has not been completed
+#[ic_cdk::update]
+fn cancel_maintenance(maintenance_id: u64) -> Result<MaintenanceSchedule, Message> {
+    let (_, before) = get_managed_maintenance(maintenance_id)?;
+    let mut maintenance = before.clone();
+    transition_maintenance(&mut maintenance, MaintenanceStatus::Cancelled)?;
+    store_maintenance(&before, &maintenance);
+    Ok(maintenance)
+}
+
+// Move a maintenance task to a new window, returning the flights it now affects
+#[ic_cdk::update]
+fn reschedule_maintenance(maintenance_id: u64, start_time: u64, end_time: u64) -> Result<MaintenanceScheduled, Message> {
+    let (_, before) = get_managed_maintenance(maintenance_id)?;
+    if before.status != MaintenanceStatus::Scheduled {
+        return Err(Message::Error("Only maintenance that has not started can be moved".to_string()));
+    }
+    check_maintenance_window(start_time, end_time)?;
+    let mut maintenance = before.clone();
+    maintenance.date = start_time;
+    maintenance.end_time = end_time;
+
+    unindex_maintenance(&before);
+    MAINTENANCE_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(maintenance_id, maintenance.clone());
+    });
+    index_maintenance(&maintenance);
+
+    record_audit(AuditAction::Update, EntityKind::MaintenanceSchedule, maintenance_id, Some(&before), Some(&maintenance));
+
+    let affected_flights = flights_affected_by(&maintenance);
+    Ok(MaintenanceScheduled {
+        maintenance,
+        affected_flights,
+    })
+}
+
+// Maintenance plans
+
+// Create a recurring maintenance plan; its instances are made by generate_planned_maintenance
+#[ic_cdk::update]
+fn create_maintenance_plan(payload: CreateMaintenancePlanPayload) -> Result<MaintenancePlan, Message> {
+    authorize(|role| role.manages(payload.airstrip_id))?;
+
+    if payload.name.is_empty() {
+        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+    }
+    check_len("Name", &payload.name, MAX_NAME_LEN)?;
+    check_len("Description", &payload.description, MAX_TEXT_LEN)?;
+    check_list("Task", &payload.tasks, MAX_TEXT_LEN)?;
+    get_active_airstrip(payload.airstrip_id)?;
+    check_maintenance_impact(payload.airstrip_id, &payload.impact, payload.runway_id)?;
+    if payload.duration_minutes == 0 || payload.duration_minutes > MAX_MAINTENANCE_WINDOW_DAYS * MINUTES_PER_DAY {
+        return Err(Message::InvalidPayload(format!(
+            "Duration must be between 1 minute and {} days",
+            MAX_MAINTENANCE_WINDOW_DAYS
+        )));
+    }
+    match payload.recurrence {
+        Recurrence::EveryDays { days: 0 } => {
+            return Err(Message::InvalidPayload("Interval must be at least one day".to_string()))
+        }
+        Recurrence::MonthlyOnDay { day } if !(1..=31).contains(&day) => {
+            return Err(Message::InvalidPayload("Day of the month must be between 1 and 31".to_string()))
+        }
+        Recurrence::AfterMovements { movements: 0 } => {
+            return Err(Message::InvalidPayload("Movement count must be at least one".to_string()))
+        }
+        _ => {}
+    }
+
+    let plan_id = next_id();
+
+    let plan = MaintenancePlan {
+        id: plan_id,
+        airstrip_id: payload.airstrip_id,
+        name: payload.name,
+        description: payload.description,
+        recurrence: payload.recurrence,
+        starts_at: payload.starts_at,
+        duration_minutes: payload.duration_minutes,
+        impact: payload.impact,
+        runway_id: payload.runway_id,
+        tasks: payload.tasks,
+        last_due: None,
+        active: true,
+        created_at: time(),
+    };
+
+    MAINTENANCE_PLANS.with(|plans| {
+        plans.borrow_mut().insert(plan_id, plan.clone());
+    });
+
+    record_audit(AuditAction::Create, EntityKind::MaintenancePlan, plan_id, None, Some(&plan));
+
+    Ok(plan)
+}
+
+#[ic_cdk::query]
+fn get_maintenance_plan(plan_id: u64) -> Result<MaintenancePlan, Message> {
+    match MAINTENANCE_PLANS.with(|plans| plans.borrow().get(&plan_id)) {
+        Some(plan) => Ok(plan),
+        None => Err(Message::NotFound("Maintenance plan not found".to_string())),
+    }
+}
+
+#[ic_cdk::query]
+fn list_maintenance_plans(airstrip_id: u64, page: PageRequest) -> Result<Page<MaintenancePlan>, Message> {
+    MAINTENANCE_PLANS.with(|plans| {
+        collect_page(&plans.borrow(), 0..=u64::MAX, &page, |_, plan| {
+            (plan.airstrip_id == airstrip_id).then_some(plan)
+        })
+    })
+}
+
+// Every plan at the airstrip, for generating and reporting what falls due
+fn maintenance_plans(airstrip_id: u64) -> Vec<MaintenancePlan> {
+    MAINTENANCE_PLANS.with(|plans| {
+        plans
+            .borrow()
+            .iter()
+            .filter(|(_, plan)| plan.airstrip_id == airstrip_id)
+            .map(|(_, plan)| plan)
+            .collect()
+    })
+}
+
+// Pause or resume a plan; paused plans generate nothing
+#[ic_cdk::update]
+fn set_maintenance_plan_active(plan_id: u64, active: bool) -> Result<MaintenancePlan, Message> {
+    let before = get_maintenance_plan(plan_id)?;
+    authorize(|role| role.manages(before.airstrip_id))?;
+
+    let mut plan = before.clone();
+    plan.active = active;
+    MAINTENANCE_PLANS.with(|plans| {
+        plans.borrow_mut().insert(plan_id, plan.clone());
+    });
+
+    record_audit(AuditAction::Update, EntityKind::MaintenancePlan, plan_id, Some(&before), Some(&plan));
+
+    Ok(plan)
+}
+
+// Generate the instances of every active plan at the airstrip that fall due
+// within the next `horizon_days`, with the flights each one affects
+#[ic_cdk::update]
+fn generate_planned_maintenance(airstrip_id: u64, horizon_days: u64) -> Result<Vec<MaintenanceScheduled>, Message> {
+    authorize(|role| role.manages(airstrip_id))?;
+    get_active_airstrip(airstrip_id)?;
+    if horizon_days > MAX_PLAN_HORIZON_DAYS {
+        return Err(Message::InvalidPayload(format!(
+            "Plans can be generated at most {} days ahead",
+            MAX_PLAN_HORIZON_DAYS
+        )));
+    }
+    let now = time();
+    let until = now.saturating_add(horizon_days * NANOS_PER_DAY);
+
+    let mut generated = Vec::new();
+    for before in maintenance_plans(airstrip_id).into_iter().filter(|plan| plan.active) {
+        let mut plan = before.clone();
+        let mut count = 0;
+        while count < MAX_INSTANCES_PER_PLAN {
+            let Some(due) = next_occurrence(&plan, now, until) else {
+                break;
+            };
+            generated.push(insert_maintenance(MaintenanceSchedule {
+                airstrip_id,
+                date: due,
+                end_time: due.saturating_add(plan.duration_minutes * NANOS_PER_MINUTE),
+                description: format!("{}: {}", plan.name, plan.description),
+                impact: plan.impact.clone(),
+                runway_id: plan.runway_id,
+                tasks: plan
+                    .tasks
+                    .iter()
+                    .map(|description| WorkTask {
+                        description: description.clone(),
+                        done: false,
+                    })
+                    .collect(),
+                plan_id: Some(plan.id),
+                ..Default::default()
+            }));
+            plan.last_due = Some(due);
+            count += 1;
+        }
+
+        if count > 0 {
+            MAINTENANCE_PLANS.with(|plans| {
+                plans.borrow_mut().insert(plan.id, plan.clone());
+            });
+            record_audit(AuditAction::Update, EntityKind::MaintenancePlan, plan.id, Some(&before), Some(&plan));
+        }
+    }
+
+    Ok(generated)
+}
+
+// Overdue and upcoming maintenance at the airstrip as of `as_of` (default now),
+// plus the next ungenerated occurrence of each active plan within the horizon
+#[ic_cdk::query]
+fn get_maintenance_due(airstrip_id: u64, as_of: Option<u64>, horizon_days: u64) -> Result<MaintenanceDue, Message> {
+    get_airstrip(airstrip_id)?;
+    if horizon_days > MAX_PLAN_HORIZON_DAYS {
+        return Err(Message::InvalidPayload(format!(
+            "The horizon cannot exceed {} days",
+            MAX_PLAN_HORIZON_DAYS
+        )));
+    }
+    let as_of = as_of.unwrap_or_else(time);
+    let until = as_of.saturating_add(horizon_days * NANOS_PER_DAY);
+
+    let ids = MAINTENANCE_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, 0, until));
+    let (mut overdue, mut upcoming) = (Vec::new(), Vec::new());
+    MAINTENANCE_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        for maintenance in ids.iter().filter_map(|id| schedules.get(id)).filter(|m| m.is_live()) {
+            let late = match maintenance.status {
+                MaintenanceStatus::Scheduled => maintenance.date < as_of,
+                _ => maintenance.window().1 <= as_of,
+            };
+            if late {
+                overdue.push(maintenance);
+            } else if maintenance.status == MaintenanceStatus::Scheduled {
+                upcoming.push(maintenance);
+            }
+        }
+    });
+
+    let plans = maintenance_plans(airstrip_id)
+        .into_iter()
+        .filter(|plan| plan.active)
+        .filter_map(|plan| {
+            next_occurrence(&plan, as_of, until).map(|next_due| PlanDue {
+                plan_id: plan.id,
+                name: plan.name,
+                next_due,
+            })
+        })
+        .collect();
+
+    Ok(MaintenanceDue {
+        airstrip_id,
+        as_of,
+        overdue,
+        upcoming,
+        plans,
+    })
+}
+
+// The plan's next occurrence after the latest generated one, if it falls by
+// `until`. A plan with nothing generated yet starts at its first occurrence at
+// or after `now`, so occurrences that have already passed are not backfilled.
+fn next_occurrence(plan: &MaintenancePlan, now: u64, until: u64) -> Option<u64> {
+    let first = plan.starts_at.max(now);
+    let due = match (&plan.recurrence, plan.last_due) {
+        (Recurrence::EveryDays { days }, None) => {
+            let period = days.saturating_mul(NANOS_PER_DAY);
+            let periods = (first - plan.starts_at).div_ceil(period);
+            plan.starts_at.saturating_add(periods.saturating_mul(period))
+        }
+        (Recurrence::EveryDays { days }, Some(last)) => last.saturating_add(days.saturating_mul(NANOS_PER_DAY)),
+        (Recurrence::MonthlyOnDay { day }, last) => {
+            let after = last.unwrap_or(first.saturating_sub(1));
+            next_monthly(after, *day, plan.starts_at % NANOS_PER_DAY)?
+        }
+        (Recurrence::AfterMovements { movements }, last) => {
+            // The nth live movement after the previous occurrence
+            let from = last.map_or(first, |last| last.saturating_add(1));
+            let ids = FLIGHTS_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), plan.airstrip_id, from, until));
+            let mut count = 0;
+            let mut due = None;
+            for flight in ids.iter().filter_map(|id| FLIGHTS.with(|flights| flights.borrow().get(id))) {
+                if flight.occupies_capacity() {
+                    count += 1;
+                    if count == *movements {
+                        due = Some(flight.movement_time());
+                        break;
+                    }
+                }
+            }
+            due?
+        }
+    };
+    (due <= until).then_some(due)
+}
+
+// First time after `after` on `day` of a month (UTC) at `time_of_day` past midnight
+fn next_monthly(after: u64, day: u32, time_of_day: u64) -> Option<u64> {
+    let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?;
+    let date = epoch.checked_add_days(chrono::Days::new(after / NANOS_PER_DAY))?;
+    let (mut year, mut month) = (date.year(), date.month());
+    loop {
+        let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+        let last_day = NaiveDate::from_ymd_opt(next_year, next_month, 1)?.pred_opt()?.day();
+        let occurrence = NaiveDate::from_ymd_opt(year, month, day.min(last_day))?;
+        let days = occurrence.signed_duration_since(epoch).num_days() as u64;
+        let due = (days * NANOS_PER_DAY).checked_add(time_of_day)?;
+        if due > after {
+            return Some(due);
+        }
+        (year, month) = (next_year, next_month);
+    }
+}
+
+// List Maintenance Schedules
+#[ic_cdk::query]
+fn list_maintenance_schedules(filter: MaintenanceFilter, page: PageRequest) -> Result<Page<MaintenanceSchedule>, Message> {
+    let start = filter.start_time.unwrap_or(0);
+    let end = filter.end_time.unwrap_or(u64::MAX);
+    let keep = |maintenance: &MaintenanceSchedule| {
+        (filter.include_archived || maintenance.archived_at.is_none())
+            && filter.status.is_none_or(|status| maintenance.status == status)
+            && (start..=end).contains(&maintenance.date)
+    };
+
+    MAINTENANCE_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        match filter.airstrip_id {
+            Some(airstrip_id) => MAINTENANCE_BY_AIRSTRIP.with(|index| {
+                collect_page(&index.borrow(), IndexKey::span(airstrip_id, start, end), &page, |key, _| {
+                    schedules.get(&key.id).filter(keep)
+                })
+            }),
+            None => collect_page(&schedules, 0..=u64::MAX, &page, |_, maintenance| Some(maintenance).filter(keep)),
+        }
+    })
+}
+
+// Stand-by-stand view of which aircraft holds each stand within [start_time, end_time)
+#[ic_cdk::query]
+fn get_stand_occupancy(airstrip_id: u64, start_time: u64, end_time: u64) -> Result<Vec<StandOccupancy>, Message> {
+    get_airstrip(airstrip_id)?;
+    if end_time <= start_time {
+        return Err(Message::InvalidPayload("End time must be after start time".to_string()));
+    }
+
+    let occupancy = airstrip_stands(airstrip_id)
+        .into_iter()
+        .map(|stand| {
+            let holdings: Vec<StandHolding> = stand_assignments(stand.id, start_time, end_time)
+                .iter()
+                .map(|a| StandHolding {
+                    assignment_id: a.id,
+                    flight_id: a.flight_id,
+                    flight_number: FLIGHTS
+                        .with(|flights| flights.borrow().get(&a.flight_id))
+                        .map(|flight| flight.flight_number)
+                        .unwrap_or_default(),
+                    aircraft_id: a.aircraft_id,
+                    registration: AIRCRAFT
+                        .with(|aircraft| aircraft.borrow().get(&a.aircraft_id))
+                        .map(|aircraft| aircraft.registration)
+                        .unwrap_or_default(),
+                    start_time: a.start_time,
+                    end_time: a.end_time,
+                })
+                .collect();
+            StandOccupancy { stand, holdings }
+        })
+        .collect();
+
+    Ok(occupancy)
+}
+
+// Projected stand occupancy at a single point in time
+#[ic_cdk::query]
+fn get_occupancy_at(airstrip_id: u64, timestamp: u64) -> Result<OccupancySnapshot, Message> {
+    let airstrip = get_airstrip(airstrip_id)?;
+
+    let flight_ids: Vec<u64> = ground_windows(airstrip_id, timestamp, timestamp.saturating_add(1), None)
+        .into_iter()
+        .map(|(id, _, _)| id)
+        .collect();
+    let occupied = flight_ids.len() as u64;
+
+    Ok(OccupancySnapshot {
+        timestamp,
+        stand_count: airstrip.capacity,
+        occupied,
+        available: airstrip.capacity.saturating_sub(occupied),
+        flight_ids,
+    })
+}
+
+// Hour-by-hour movements and peak stand occupancy across [start_time, end_time)
+#[ic_cdk::query]
+fn get_occupancy_window(airstrip_id: u64, start_time: u64, end_time: u64) -> Result<Vec<HourlySlotUsage>, Message> {
+    let airstrip = get_airstrip(airstrip_id)?;
+
+    if end_time <= start_time {
+        return Err(Message::InvalidPayload("End time must be after start time".to_string()));
+    }
+    let first_slot = start_time / NANOS_PER_HOUR * NANOS_PER_HOUR;
+    let hours = (end_time - first_slot).div_ceil(NANOS_PER_HOUR);
+    if hours > MAX_OCCUPANCY_WINDOW_HOURS {
+        return Err(Message::InvalidPayload(format!(
+            "Window cannot span more than {} hours",
+            MAX_OCCUPANCY_WINDOW_HOURS
+        )));
+    }
+
+    let ids = SLOTS_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, first_slot, end_time - 1));
+    let reservations: Vec<SlotReservation> = SLOT_RESERVATIONS.with(|slots| {
+        let slots = slots.borrow();
+        ids.iter()
+            .filter_map(|id| slots.get(id))
+            .filter(|slot| slot.released_at.is_none())
+            .collect()
+    });
+
+    let usage = (0..hours)
+        .map(|hour| {
+            let slot_start = first_slot + hour * NANOS_PER_HOUR;
+            let in_slot = reservations.iter().filter(|slot| slot.slot_start == slot_start);
+            let arrivals = in_slot.clone().filter(|slot| slot.movement == MovementType::Arrival).count() as u64;
+            let departures = in_slot.filter(|slot| slot.movement == MovementType::Departure).count() as u64;
+
+            HourlySlotUsage {
+                slot_start,
+                arrivals,
+                departures,
+                movements_per_hour: airstrip.movements_per_hour,
+                peak_occupancy: peak_occupancy(airstrip_id, slot_start, slot_start + NANOS_PER_HOUR, None),
+                stand_count: airstrip.capacity,
+            }
+        })
+        .collect();
+
+    Ok(usage)
+}
+
+// Flight time limitations
+
+// Current duty, flight time and rest limits
+#[ic_cdk::query]
+fn get_ftl_limits() -> FtlLimits {
+    FTL_LIMITS.with(|limits| limits.borrow().get().clone())
+}
+
+// Replace the duty, flight time and rest limits
+#[ic_cdk::update]
+fn set_ftl_limits(limits: FtlLimits) -> Result<FtlLimits, Message> {
+    require_admin()?;
+
+    // A window cannot hold more minutes than it is long
+    for window in FtlWindow::ALL {
+        let window_minutes = window.length() / NANOS_PER_MINUTE;
+        for (kind, limit) in [("Duty", limits.duty_minutes.get(window)), ("Flight time", limits.flight_minutes.get(window))] {
+            if limit > window_minutes {
+                return Err(Message::InvalidPayload(format!(
+                    "{} limit for {:?} cannot exceed {} minutes",
+                    kind, window, window_minutes
+                )));
+            }
+        }
+    }
+    if limits.min_rest_minutes > MAX_MIN_REST_MINUTES {
+        return Err(Message::InvalidPayload(format!(
+            "Minimum rest cannot exceed {} minutes",
+            MAX_MIN_REST_MINUTES
+        )));
+    }
+
+    let before = get_ftl_limits();
+    FTL_LIMITS
+        .with(|cell| cell.borrow_mut().set(limits.clone()))
+        .expect("Cannot store the FTL limits");
+    record_audit(AuditAction::Update, EntityKind::FtlLimits, 0, Some(&before), Some(&limits));
+    Ok(limits)
+}
+
+// Duty and flight-time usage over each rolling window ending at `as_of` (default now)
+#[ic_cdk::query]
+fn get_duty_allowance(pilot_id: u64, as_of: Option<u64>) -> Result<DutyAllowance, Message> {
+    get_pilot(pilot_id)?;
+    let as_of = as_of.unwrap_or_else(time);
+    let limits = get_ftl_limits();
+
+    let duties = pilot_duties(pilot_id, as_of.saturating_sub(FtlWindow::Days365.length()), as_of);
+    let usage = |limits: &WindowLimits, flight_time: bool| -> Vec<WindowUsage> {
+        FtlWindow::ALL
+            .iter()
+            .map(|&window| {
+                let window_start = as_of.saturating_sub(window.length());
+                let used: u64 = duties
+                    .iter()
+                    .map(|duty| if flight_time { duty.1 } else { duty.0 })
+                    .map(|(start, end)| overlap(start, end, window_start, as_of))
+                    .sum::<u64>()
+                    / NANOS_PER_MINUTE;
+                let limit = limits.get(window);
+                WindowUsage {
+                    window,
+                    limit_minutes: limit,
+                    used_minutes: used,
+                    remaining_minutes: (limit > 0).then(|| limit.saturating_sub(used)),
+                }
+            })
+            .collect()
+    };
+
+    Ok(DutyAllowance {
+        pilot_id,
+        as_of,
+        duty: usage(&limits.duty_minutes, false),
+        flight: usage(&limits.flight_minutes, true),
+        rested_at: duties
+            .iter()
+            .map(|(duty, _)| duty.1)
+            .filter(|end| *end <= as_of)
+            .max()
+            .map(|end| end.saturating_add(limits.min_rest_minutes.saturating_mul(NANOS_PER_MINUTE))),
+    })
+}
+
+// A duty window and the flight-time window inside it
+type DutyTimes = ((u64, u64), (u64, u64));
+
+// A stored schedule's id with its duty and flight-time windows
+type StoredDuty = (u64, DutyTimes);
+
+// Duty window and flight-time window of each live schedule of the pilot that overlaps [start, end)
+fn pilot_duties(pilot_id: u64, start: u64, end: u64) -> Vec<DutyTimes> {
+    stored_duties(pilot_id, start, end).into_iter().map(|(_, times)| times).collect()
+}
+
+// The pilot's live duties that bear on a new duty: those within the longest FTL window of it
+fn duties_around(pilot_id: u64, duty: (u64, u64)) -> Vec<StoredDuty> {
+    let longest = FtlWindow::Days365.length();
+    stored_duties(pilot_id, duty.0.saturating_sub(longest), duty.1.saturating_add(longest))
+}
+
+fn stored_duties(pilot_id: u64, start: u64, end: u64) -> Vec<StoredDuty> {
+    let ids = SCHEDULES_BY_PILOT.with(|index| {
+        index_ids(&index.borrow(), pilot_id, start.saturating_sub(MAX_DUTY_LENGTH), end.saturating_sub(1))
+    });
+    let schedules: Vec<PilotSchedule> = PILOT_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        ids.iter()
+            .filter_map(|id| schedules.get(id))
+            .filter(|schedule| schedule.status != "cancelled" && schedule.start_time < end && schedule.end_time > start)
+            .collect()
+    });
+
+    schedules
+        .into_iter()
+        .map(|schedule| {
+            let duty = (schedule.start_time, schedule.end_time);
+            (schedule.id, (duty, flight_time_within(schedule.flight_id, duty)))
+        })
+        .collect()
+}
+
+// The part of the flight's departure-to-arrival time that falls inside the duty
+fn flight_time_within(flight_id: u64, duty: (u64, u64)) -> (u64, u64) {
+    match FLIGHTS.with(|flights| flights.borrow().get(&flight_id)) {
+        Some(flight) => {
+            let start = flight.departure_time.clamp(duty.0, duty.1);
+            (start, flight.arrival_time.clamp(start, duty.1))
+        }
+        None => (duty.0, duty.0),
+    }
+}
+
+fn overlap(start: u64, end: u64, window_start: u64, window_end: u64) -> u64 {
+    end.min(window_end).saturating_sub(start.max(window_start))
+}
+
+// Largest total any `window`-long period overlapping [start, end) collects from
+// the intervals. The total peaks when a period ends at an interval's end or
+// starts at an interval's start, so only those periods are checked.
+fn peak_window_total(intervals: &[(u64, u64)], window: u64, start: u64, end: u64) -> u64 {
+    // Only intervals within a window of [start, end) can fall in such a period
+    let intervals: Vec<(u64, u64)> = intervals
+        .iter()
+        .copied()
+        .filter(|&(s, e)| e > start.saturating_sub(window) && s < end.saturating_add(window))
+        .collect();
+    intervals
+        .iter()
+        .flat_map(|&(interval_start, interval_end)| [interval_end, interval_start.saturating_add(window)])
+        .filter(|&period_end| period_end > start && period_end.saturating_sub(window) < end)
+        .map(|period_end| {
+            let period_start = period_end.saturating_sub(window);
+            intervals
+                .iter()
+                .map(|&(s, e)| overlap(s, e, period_start, period_end))
+                .sum::<u64>()
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+// Checks a new duty against every FTL limit and the minimum rest, given the
+// pilot's other duties within the longest FTL window of it
+fn check_ftl(others: &[DutyTimes], duty: (u64, u64), flight_time: (u64, u64)) -> Result<(), FtlViolation> {
+    let limits = get_ftl_limits();
+    let mut duties = others.to_vec();
+
+    let min_rest = limits.min_rest_minutes.saturating_mul(NANOS_PER_MINUTE);
+    for ((other_start, other_end), _) in &duties {
+        let gap = if *other_end <= duty.0 {
+            duty.0 - other_end
+        } else if *other_start >= duty.1 {
+            other_start - duty.1
+        } else {
+            continue;
+        };
+        if gap < min_rest {
+            return Err(FtlViolation::InsufficientRest {
+                required_minutes: limits.min_rest_minutes,
+                available_minutes: gap / NANOS_PER_MINUTE,
+            });
+        }
+    }
+
+    duties.push((duty, flight_time));
+    let duty_intervals: Vec<(u64, u64)> = duties.iter().map(|(duty, _)| *duty).collect();
+    let flight_intervals: Vec<(u64, u64)> = duties.iter().map(|(_, flight)| *flight).collect();
+
+    for window in FtlWindow::ALL {
+        let limit = limits.duty_minutes.get(window);
+        let scheduled = peak_window_total(&duty_intervals, window.length(), duty.0, duty.1);
+        if limit > 0 && scheduled > limit.saturating_mul(NANOS_PER_MINUTE) {
+            return Err(FtlViolation::DutyTime {
+                window,
+                limit_minutes: limit,
+                scheduled_minutes: scheduled / NANOS_PER_MINUTE,
+            });
+        }
+
+        let limit = limits.flight_minutes.get(window);
+        let scheduled = peak_window_total(&flight_intervals, window.length(), duty.0, duty.1);
+        if limit > 0 && scheduled > limit.saturating_mul(NANOS_PER_MINUTE) {
+            return Err(FtlViolation::FlightTime {
+                window,
+                limit_minutes: limit,
+                scheduled_minutes: scheduled / NANOS_PER_MINUTE,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+// Pilot Scheduling
+#[ic_cdk::update]
+fn schedule_pilot(pilot_id: u64, flight_id: u64, start_time: u64, end_time: u64, role: CrewRole) -> Result<PilotSchedule, Message> {
+    if end_time <= start_time {
+        return Err(Message::InvalidPayload("End time must be after start time".to_string()));
+    }
+    if end_time - start_time > MAX_DUTY_LENGTH {
+        return Err(Message::InvalidPayload("A duty cannot be longer than 24 hours".to_string()));
+    }
+
+    // Verify pilot exists
+    let pilot = get_pilot(pilot_id)?;
+
+    // Verify flight exists
+    let flight = match FLIGHTS.with(|flights| flights.borrow().get(&flight_id)) {
+        Some(flight) => flight,
+        None => return Err(Message::NotFound("Flight not found".to_string())),
+    };
+
+    authorize(|role| *role == Role::Dispatcher || role.manages(flight.airstrip_id))?;
+
+    let aircraft = get_aircraft(flight.aircraft_id)?;
+    let duty = (start_time, end_time);
+    check_assignment(&pilot, &flight, &aircraft, duty, role, &duties_around(pilot_id, duty), &[], None)?;
+
+    Ok(insert_schedule(pilot_id, flight_id, (start_time, end_time), role))
+}
+
+// Checks the crew member can take the role on the flight for the duty. `stored`
+// holds the pilot's live duties around this one (see duties_around), and
+// `planned` schedules that are not stored yet count as if they were. A schedule
+// being moved is named in `exclude_schedule` so it neither clashes with its new
+// duty nor holds its own seat.
+#[allow(clippy::too_many_arguments)]
+fn check_assignment(
+    pilot: &Pilot,
+    flight: &Flight,
+    aircraft: &Aircraft,
+    duty: (u64, u64),
+    role: CrewRole,
+    stored: &[StoredDuty],
+    planned: &[PilotSchedule],
+    exclude_schedule: Option<u64>,
+) -> Result<(), Message> {
+    let (start_time, end_time) = duty;
+    if !pilot.can_fill(role) {
+        return Err(Message::PilotIneligible(IneligibilityReason::RoleNotHeld { role }));
+    }
+
+    // Licence, medical and type rating must all hold until the duty ends
+    if role.is_flight_crew() {
+        pilot.check_eligibility(aircraft, end_time).map_err(Message::PilotIneligible)?;
+        let policy = get_recency_policy();
+        if policy.enforce {
+            check_recency(&policy, pilot.id, flight, aircraft, start_time).map_err(Message::PilotIneligible)?;
+        }
+    }
+
+    // A flight has one pilot in command and at most the second pilots it needs
+    let required = required_complement(flight, aircraft);
+    let mut rostered = rostered_complement(flight.id, exclude_schedule);
+    for schedule in planned.iter().filter(|schedule| schedule.flight_id == flight.id) {
+        rostered.add(schedule.role);
+    }
+    if matches!(role, CrewRole::PilotInCommand | CrewRole::SecondInCommand)
+        && rostered.count(role) >= required.count(role).max(1)
+    {
+        return Err(Message::Error(format!("Flight already has its {:?}", role)));
+    }
+
+    // Check pilot availability
+    let others: Vec<DutyTimes> = stored
+        .iter()
+        .filter(|(id, _)| Some(*id) != exclude_schedule)
+        .map(|(_, times)| *times)
+        .chain(planned.iter().filter(|schedule| schedule.pilot_id == pilot.id).map(|schedule| {
+            let duty = (schedule.start_time, schedule.end_time);
+            (duty, flight_time_within(schedule.flight_id, duty))
+        }))
+        .collect();
+    let is_available = others
+        .iter()
+        .all(|((other_start, other_end), _)| !(start_time < *other_end && end_time > *other_start));
+
+    if !is_available {
+        return Err(Message::Error("Pilot is not available for this time slot".to_string()));
+    }
+    check_availability(pilot.id, flight.airstrip_id, duty).map_err(Message::PilotIneligible)?;
+
+    check_ftl(&others, duty, flight_time_within(flight.id, duty)).map_err(Message::FtlExceeded)?;
+
+    Ok(())
+}
+
+fn insert_schedule(pilot_id: u64, flight_id: u64, duty: (u64, u64), role: CrewRole) -> PilotSchedule {
+    let schedule_id = next_id();
+
+    let schedule = PilotSchedule {
+        id: schedule_id,
+        pilot_id,
+        flight_id,
+        start_time: duty.0,
+        end_time: duty.1,
+        status: "scheduled".to_string(),
+        role,
+    };
+
+    PILOT_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(schedule_id, schedule.clone());
+    });
+    index_pilot_schedule(&schedule);
+
+    record_audit(AuditAction::Create, EntityKind::PilotSchedule, schedule_id, None, Some(&schedule));
+
+    schedule
+}
+
+fn set_schedule_status(mut schedule: PilotSchedule, status: &str) -> PilotSchedule {
+    let before = schedule.clone();
+    schedule.status = status.to_string();
+    PILOT_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(schedule.id, schedule.clone());
+    });
+    record_audit(AuditAction::Update, EntityKind::PilotSchedule, schedule.id, Some(&before), Some(&schedule));
+    schedule
+}
+
+// Moves a live schedule to a new duty after running every roster check with the
+// schedule itself left out. Nothing is written when a check fails.
+fn move_schedule(schedule: &PilotSchedule, duty: (u64, u64)) -> Result<PilotSchedule, Message> {
+    let flight = match FLIGHTS.with(|flights| flights.borrow().get(&schedule.flight_id)) {
+        Some(flight) => flight,
+        None => return Err(Message::NotFound("Flight not found".to_string())),
+    };
+    let aircraft = get_aircraft(flight.aircraft_id)?;
+    check_schedule_move(schedule, &flight, &aircraft, duty)?;
+
+    Ok(store_schedule_move(schedule, duty))
+}
+
+// Checks the schedule's crew member could work `duty` on the flight as given,
+// which may be the flight at times it has not been moved to yet
+fn check_schedule_move(
+    schedule: &PilotSchedule,
+    flight: &Flight,
+    aircraft: &Aircraft,
+    duty: (u64, u64),
+) -> Result<(), Message> {
+    if duty.1 <= duty.0 {
+        return Err(Message::InvalidPayload("End time must be after start time".to_string()));
+    }
+    if duty.1 - duty.0 > MAX_DUTY_LENGTH {
+        return Err(Message::InvalidPayload("A duty cannot be longer than 24 hours".to_string()));
+    }
+    let pilot = get_pilot(schedule.pilot_id)?;
+    let stored = duties_around(pilot.id, duty);
+    check_assignment(&pilot, flight, aircraft, duty, schedule.role, &stored, &[], Some(schedule.id))
+}
+
+fn store_schedule_move(schedule: &PilotSchedule, duty: (u64, u64)) -> PilotSchedule {
+    let mut moved = schedule.clone();
+    moved.start_time = duty.0;
+    moved.end_time = duty.1;
+    unindex_pilot_schedule(schedule);
+    PILOT_SCHEDULES.with(|schedules| schedules.borrow_mut().insert(moved.id, moved.clone()));
+    index_pilot_schedule(&moved);
+
+    record_audit(AuditAction::Update, EntityKind::PilotSchedule, moved.id, Some(schedule), Some(&moved));
+
+    moved
+}
+
+fn get_live_schedule(schedule_id: u64) -> Result<PilotSchedule, Message> {
+    let schedule = match PILOT_SCHEDULES.with(|schedules| schedules.borrow().get(&schedule_id)) {
+        Some(schedule) => schedule,
+        None => return Err(Message::NotFound("Pilot schedule not found".to_string())),
+    };
+    let airstrip_id = FLIGHTS
+        .with(|flights| flights.borrow().get(&schedule.flight_id))
+        .map(|flight| flight.airstrip_id);
+    authorize(|role| *role == Role::Dispatcher || airstrip_id.is_some_and(|id| role.manages(id)))?;
+
+    if schedule.status != "scheduled" {
+        return Err(Message::Error(format!("Pilot schedule is already {}", schedule.status)));
+    }
+    Ok(schedule)
+}
+
+// Cancel a pilot schedule
+#[ic_cdk::update]
+fn cancel_pilot_schedule(schedule_id: u64) -> Result<PilotSchedule, Message> {
+    let schedule = get_live_schedule(schedule_id)?;
+    Ok(set_schedule_status(schedule, "cancelled"))
+}
+
+// Move a pilot schedule to a new duty window, re-checking availability and limits
+#[ic_cdk::update]
+fn reschedule_pilot_schedule(schedule_id: u64, start_time: u64, end_time: u64) -> Result<PilotSchedule, Message> {
+    let schedule = get_live_schedule(schedule_id)?;
+    move_schedule(&schedule, (start_time, end_time))
+}
+
+// Auto-rostering
+
+// Crews every flight moving within [from, to] that still lacks crew. Flights are
+// taken in departure order and each seat goes to the eligible crew member with
+// the fewest duty minutes in the horizon, so hours stay balanced. Without
+// `commit` the assignments are only proposed. A call covers at most
+// MAX_ROSTER_FLIGHTS flights; `resume_from` says where the next call starts.
+#[ic_cdk::update]
+fn auto_roster(request: RosterRequest) -> Result<RosterResult, Message> {
+    if request.to < request.from {
+        return Err(Message::InvalidPayload("Roster end must not be before its start".to_string()));
+    }
+    if request.to - request.from > MAX_ROSTER_HORIZON_DAYS * NANOS_PER_DAY {
+        return Err(Message::InvalidPayload(format!(
+            "A roster cannot cover more than {} days",
+            MAX_ROSTER_HORIZON_DAYS
+        )));
+    }
+    let resume_at = match &request.cursor {
+        Some(cursor) => parse_position(cursor)
+            .ok_or_else(|| Message::InvalidPayload("Invalid roster cursor".to_string()))?,
+        None => (request.from, 0),
+    };
+
+    let airstrip_ids: Vec<u64> = match request.airstrip_id {
+        Some(airstrip_id) => {
+            let airstrip = get_airstrip(airstrip_id)?;
+            authorize(|role| *role == Role::Dispatcher || role.manages(airstrip.id))?;
+            vec![airstrip.id]
+        }
+        None => {
+            authorize(|role| *role == Role::Dispatcher)?;
+            AIRSTRIPS.with(|airstrips| {
+                airstrips
+                    .borrow()
+                    .iter()
+                    .filter(|(_, airstrip)| airstrip.archived_at.is_none())
+                    .map(|(id, _)| id)
+                    .collect()
+            })
+        }
+    };
+
+    let mut flights: Vec<Flight> = Vec::new();
+    for airstrip_id in airstrip_ids {
+        let ids = FLIGHTS_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, request.from, request.to));
+        flights.extend(
+            ids.iter()
+                .filter_map(|id| FLIGHTS.with(|flights| flights.borrow().get(id)))
+                .filter(|flight| flight.awaiting_departure() && (flight.movement_time(), flight.id) >= resume_at),
+        );
+    }
+
+    // Keep each call within the instruction limit: roster the earliest flights
+    // and hand back where the next call should pick up
+    flights.sort_by_key(|flight| (flight.movement_time(), flight.id));
+    let resume_from = flights
+        .get(MAX_ROSTER_FLIGHTS)
+        .map(|flight| position_cursor((flight.movement_time(), flight.id)));
+    flights.truncate(MAX_ROSTER_FLIGHTS);
+    flights.sort_by_key(|flight| (flight.departure_time, flight.id));
+
+    // Every pilot's stored duties are loaded once, wide enough for any duty in the horizon
+    let reach = FtlWindow::Days365.length() + MAX_DUTY_LENGTH;
+    let pilots: Vec<Pilot> = PILOTS.with(|pilots| pilots.borrow().iter().map(|(_, pilot)| pilot).collect());
+    let duties: HashMap<u64, Vec<StoredDuty>> = pilots
+        .iter()
+        .map(|pilot| {
+            let start = request.from.saturating_sub(reach);
+            (pilot.id, stored_duties(pilot.id, start, request.to.saturating_add(reach)))
+        })
+        .collect();
+    let mut load_minutes: HashMap<u64, u64> = duties
+        .iter()
+        .map(|(pilot_id, duties)| {
+            let nanos = duties
+                .iter()
+                .map(|(_, (duty, _))| overlap(duty.0, duty.1, request.from, request.to))
+                .sum::<u64>();
+            (*pilot_id, nanos / NANOS_PER_MINUTE)
+        })
+        .collect();
+
+    let mut planned: Vec<PilotSchedule> = Vec::new();
+    let mut gaps: Vec<RosterGap> = Vec::new();
+    for flight in &flights {
+        let gap = |role: CrewRole, missing: u64, reason: String, rejections: Vec<CandidateRejection>| RosterGap {
+            flight_id: flight.id,
+            flight_number: flight.flight_number.clone(),
+            role,
+            missing,
+            reason,
+            rejections,
+        };
+
+        let aircraft = match get_aircraft(flight.aircraft_id) {
+            Ok(aircraft) => aircraft,
+            Err(_) => {
+                gaps.push(gap(CrewRole::PilotInCommand, 1, "Aircraft not found".to_string(), Vec::new()));
+                continue;
+            }
+        };
+        let shortfall = rostered_complement(flight.id, None).shortfall(&required_complement(flight, &aircraft));
+        if shortfall.is_empty() {
+            continue;
+        }
+
+        let duty = (
+            flight.departure_time.saturating_sub(DUTY_REPORT_MINUTES * NANOS_PER_MINUTE),
+            flight.arrival_time.saturating_add(DUTY_RELEASE_MINUTES * NANOS_PER_MINUTE),
+        );
+        if duty.1 <= duty.0 || duty.1 - duty.0 > MAX_DUTY_LENGTH {
+            for CrewShortfall { role, missing } in shortfall {
+                gaps.push(gap(role, missing, "Duty would be longer than 24 hours".to_string(), Vec::new()));
+            }
+            continue;
+        }
+
+        let mut on_flight: Vec<u64> = flight_schedules(flight.id).iter().map(|schedule| schedule.pilot_id).collect();
+        for CrewShortfall { role, missing } in shortfall {
+            for filled in 0..missing {
+                let mut candidates: Vec<&Pilot> = pilots
+                    .iter()
+                    .filter(|pilot| pilot.can_fill(role) && !on_flight.contains(&pilot.id))
+                    .collect();
+                candidates.sort_by_key(|pilot| (load_minutes.get(&pilot.id).copied().unwrap_or(0), pilot.id));
+
+                let mut rejections = Vec::new();
+                let chosen = candidates.iter().find(|pilot| {
+                    let stored = duties.get(&pilot.id).map(Vec::as_slice).unwrap_or_default();
+                    match check_assignment(pilot, flight, &aircraft, duty, role, stored, &planned, None) {
+                        Ok(()) => true,
+                        Err(reason) => {
+                            rejections.push(CandidateRejection { pilot_id: pilot.id, reason });
+                            false
+                        }
+                    }
+                });
+
+                match chosen {
+                    Some(pilot) => {
+                        *load_minutes.entry(pilot.id).or_insert(0) += (duty.1 - duty.0) / NANOS_PER_MINUTE;
+                        on_flight.push(pilot.id);
+                        planned.push(PilotSchedule {
+                            id: 0,
+                            pilot_id: pilot.id,
+                            flight_id: flight.id,
+                            start_time: duty.0,
+                            end_time: duty.1,
+                            status: "scheduled".to_string(),
+                            role,
+                        });
+                    }
+                    None => {
+                        let reason = if candidates.is_empty() {
+                            "No available crew member holds this role"
+                        } else {
+                            "Every crew member holding this role was rejected"
+                        };
+                        gaps.push(gap(role, missing - filled, reason.to_string(), rejections));
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    let assignments = if request.commit {
+        planned
+            .into_iter()
+            .map(|schedule| insert_schedule(schedule.pilot_id, schedule.flight_id, (schedule.start_time, schedule.end_time), schedule.role))
+            .collect()
+    } else {
+        planned
+    };
+
+    Ok(RosterResult {
+        committed: request.commit,
+        assignments,
+        gaps,
+        resume_from,
+    })
+}
+
+// Crew complement
+
+// Crew the flight needs: pilots from the aircraft, cabin crew from its seats,
+// and a flight nurse on medevac flights
+fn required_complement(flight: &Flight, aircraft: &Aircraft) -> CrewComplement {
+    let cabin_crew = match flight.category {
+        FlightCategory::Passenger | FlightCategory::Charter if aircraft.seat_count >= CABIN_CREW_MIN_SEATS => {
+            aircraft.seat_count.div_ceil(SEATS_PER_CABIN_CREW)
+        }
+        _ => 0,
+    };
+    let flight_crew = match flight.category {
+        // Training flights carry an instructor alongside the trainee
+        FlightCategory::Training => aircraft.flight_crew().max(2),
+        _ => aircraft.flight_crew(),
+    };
+
+    CrewComplement {
+        pilots_in_command: 1,
+        second_in_command: flight_crew.saturating_sub(1),
+        cabin_crew,
+        flight_nurses: u64::from(flight.category == FlightCategory::Medevac),
+    }
+}
+
+// Live schedules on the flight
+fn flight_schedules(flight_id: u64) -> Vec<PilotSchedule> {
+    let ids = SCHEDULES_BY_FLIGHT.with(|index| index_ids(&index.borrow(), flight_id, 0, u64::MAX));
+    PILOT_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        ids.iter()
+            .filter_map(|id| schedules.get(id))
+            .filter(|schedule| schedule.status != "cancelled")
+            .collect()
+    })
+}
+
+fn rostered_complement(flight_id: u64, exclude_schedule: Option<u64>) -> CrewComplement {
+    let mut rostered = CrewComplement::default();
+    for schedule in flight_schedules(flight_id).into_iter().filter(|schedule| Some(schedule.id) != exclude_schedule) {
+        rostered.add(schedule.role);
+    }
+    rostered
+}
+
+fn flight_crew_status(flight: &Flight) -> FlightCrew {
+    let aircraft = AIRCRAFT
+        .with(|aircraft| aircraft.borrow().get(&flight.aircraft_id))
+        .unwrap_or_default();
+    let required = required_complement(flight, &aircraft);
+    let schedules = flight_schedules(flight.id);
+    let mut rostered = CrewComplement::default();
+    for schedule in &schedules {
+        rostered.add(schedule.role);
+    }
+
+    FlightCrew {
+        flight_id: flight.id,
+        flight_number: flight.flight_number.clone(),
+        airstrip_id: flight.airstrip_id,
+        departure_time: flight.departure_time,
+        category: flight.category,
+        shortfall: rostered.shortfall(&required),
+        required,
+        rostered,
+        schedules,
+    }
+}
+
+// Required and rostered crew for a flight
+#[ic_cdk::query]
+fn get_flight_crew(flight_id: u64) -> Result<FlightCrew, Message> {
+    match FLIGHTS.with(|flights| flights.borrow().get(&flight_id)) {
+        Some(flight) => Ok(flight_crew_status(&flight)),
+        None => Err(Message::NotFound("Flight not found".to_string())),
+    }
+}
+
+// Flights moving within [start_time, end_time] that have not departed yet and lack crew
+#[ic_cdk::query]
+fn get_undercrewed_flights(airstrip_id: Option<u64>, start_time: u64, end_time: u64) -> Result<Vec<FlightCrew>, Message> {
+    if end_time < start_time {
+        return Err(Message::InvalidPayload("End time must not be before start time".to_string()));
+    }
+    let airstrip_ids: Vec<u64> = match airstrip_id {
+        Some(airstrip_id) => vec![get_airstrip(airstrip_id)?.id],
+        None => AIRSTRIPS.with(|airstrips| airstrips.borrow().iter().map(|(id, _)| id).collect()),
+    };
+
+    let mut undercrewed = Vec::new();
+    for airstrip_id in airstrip_ids {
+        let ids = FLIGHTS_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, start_time, end_time));
+        for flight in ids.iter().filter_map(|id| FLIGHTS.with(|flights| flights.borrow().get(id))) {
+            if !flight.awaiting_departure() {
+                continue;
+            }
+            let crew = flight_crew_status(&flight);
+            if !crew.shortfall.is_empty() {
+                undercrewed.push(crew);
+            }
+        }
+    }
+
+    undercrewed.sort_by_key(|crew| crew.departure_time);
+    Ok(undercrewed)
+}
+
+// Emergency Protocols
+#[ic_cdk::update]
+fn create_emergency_protocol(
+    airstrip_id: u64,
+    protocol_type: ProtocolType,
+    description: String,
+    contact_numbers: Vec<String>,
+    evacuation_routes: Vec<String>,
+) -> Result<EmergencyProtocol, Message> {
+    let caller = authorize(|role| role.manages(airstrip_id))?;
+    get_active_airstrip(airstrip_id)?;
+
+    check_protocol(&protocol_type, &description, &contact_numbers, &evacuation_routes)?;
+
+    let protocol_id = next_id();
+
+    let now = time();
+    let protocol = EmergencyProtocol {
+        id: protocol_id,
+        airstrip_id,
+        protocol_type,
+        description,
+        contact_numbers: contact_numbers.iter().map(|number| number.trim().to_string()).collect(),
+        evacuation_routes,
+        created_at: now,
+        archived_at: None,
+        revision: 1,
+        approved_by: Some(caller),
+        approved_at: now,
+    };
+
+    EMERGENCY_PROTOCOLS.with(|protocols| {
+        protocols.borrow_mut().insert(protocol_id, protocol.clone());
+    });
+    index_protocol(&protocol);
+    insert_protocol_revision(&protocol, "Initial revision".to_string());
+
+    record_audit(AuditAction::Create, EntityKind::EmergencyProtocol, protocol_id, None, Some(&protocol));
+
+    Ok(protocol)
+}
+
+fn check_protocol(
+    protocol_type: &ProtocolType,
+    description: &str,
+    contact_numbers: &[String],
+    evacuation_routes: &[String],
+) -> Result<(), Message> {
+    if let ProtocolType::Other { name } = protocol_type {
+        if name.trim().is_empty() {
+            return Err(Message::InvalidPayload("Protocol type name is required".to_string()));
+        }
+        check_len("Protocol type", name, MAX_NAME_LEN)?;
+    }
+    if description.trim().is_empty() {
+        return Err(Message::InvalidPayload("Protocol description is required".to_string()));
+    }
+    check_len("Description", description, MAX_TEXT_LEN)?;
+    if contact_numbers.is_empty() {
+        return Err(Message::InvalidPayload("At least one contact number is required".to_string()));
+    }
+    check_list("Contact numbers", contact_numbers, MAX_NAME_LEN)?;
+    for number in contact_numbers {
+        check_phone_number(number)?;
+    }
+    check_list("Evacuation routes", evacuation_routes, MAX_TEXT_LEN)
+}
+
+// Stores a protocol's current text as one entry of its revision history
+fn insert_protocol_revision(protocol: &EmergencyProtocol, change_summary: String) {
+    let revision_id = next_id();
+
+    let revision = ProtocolRevision {
+        id: revision_id,
+        protocol_id: protocol.id,
+        revision: protocol.revision,
+        protocol_type: protocol.protocol_type.clone(),
+        description: protocol.description.clone(),
+        contact_numbers: protocol.contact_numbers.clone(),
+        evacuation_routes: protocol.evacuation_routes.clone(),
+        change_summary,
+        approved_by: protocol.approved_by,
+        approved_at: protocol.approved_at,
+    };
+
+    PROTOCOL_REVISIONS.with(|revisions| {
+        revisions.borrow_mut().insert(revision_id, revision.clone());
+    });
+    index_protocol_revision(&revision);
+}
+
+// Loads a protocol that is still in force for a manager of its airstrip
+fn get_managed_protocol(protocol_id: u64) -> Result<(Principal, EmergencyProtocol), Message> {
+    let protocol = match EMERGENCY_PROTOCOLS.with(|protocols| protocols.borrow().get(&protocol_id)) {
+        Some(protocol) => protocol,
+        None => return Err(Message::NotFound("Emergency protocol not found".to_string())),
+    };
+    let caller = authorize(|role| role.manages(protocol.airstrip_id))?;
+    if protocol.archived_at.is_some() {
+        return Err(Message::Error("Emergency protocol has been retired".to_string()));
+    }
+    Ok((caller, protocol))
+}
+
+// Update Emergency Protocol: publishes a new revision approved by the caller
+#[ic_cdk::update]
+fn update_emergency_protocol(protocol_id: u64, payload: UpdateProtocolPayload) -> Result<EmergencyProtocol, Message> {
+    let (caller, mut protocol) = get_managed_protocol(protocol_id)?;
+    let before = protocol.clone();
+
+    if payload.change_summary.trim().is_empty() {
+        return Err(Message::InvalidPayload("A change summary is required".to_string()));
+    }
+    check_len("Change summary", &payload.change_summary, MAX_TEXT_LEN)?;
+
+    if let Some(protocol_type) = payload.protocol_type {
+        protocol.protocol_type = protocol_type;
+    }
+    if let Some(description) = payload.description {
+        protocol.description = description;
+    }
+    if let Some(contact_numbers) = payload.contact_numbers {
+        protocol.contact_numbers = contact_numbers.iter().map(|number| number.trim().to_string()).collect();
+    }
+    if let Some(evacuation_routes) = payload.evacuation_routes {
+        protocol.evacuation_routes = evacuation_routes;
+    }
+    check_protocol(
+        &protocol.protocol_type,
+        &protocol.description,
+        &protocol.contact_numbers,
+        &protocol.evacuation_routes,
+    )?;
+
+    protocol.revision += 1;
+    protocol.approved_by = Some(caller);
+    protocol.approved_at = time();
+
+    EMERGENCY_PROTOCOLS.with(|protocols| {
+        protocols.borrow_mut().insert(protocol_id, protocol.clone());
+    });
+    insert_protocol_revision(&protocol, payload.change_summary);
+
+    record_audit(AuditAction::Update, EntityKind::EmergencyProtocol, protocol_id, Some(&before), Some(&protocol));
+
+    Ok(protocol)
+}
+
+// Retire Emergency Protocol: takes it out of force; its history is kept
+#[ic_cdk::update]
+fn retire_emergency_protocol(protocol_id: u64) -> Result<EmergencyProtocol, Message> {
+    let (_, mut protocol) = get_managed_protocol(protocol_id)?;
+    let before = protocol.clone();
+
+    protocol.archived_at = Some(time());
+    EMERGENCY_PROTOCOLS.with(|protocols| {
+        protocols.borrow_mut().insert(protocol_id, protocol.clone());
+    });
+
+    record_audit(AuditAction::Archive, EntityKind::EmergencyProtocol, protocol_id, Some(&before), Some(&protocol));
+
+    Ok(protocol)
+}
+
+// Get Emergency Protocol: the current revision and who approved it
+#[ic_cdk::query]
+fn get_emergency_protocol(protocol_id: u64) -> Result<EmergencyProtocol, Message> {
+    match EMERGENCY_PROTOCOLS.with(|protocols| protocols.borrow().get(&protocol_id)) {
+        Some(protocol) => Ok(protocol),
+        None => Err(Message::NotFound("Emergency protocol not found".to_string())),
+    }
+}
+
+// Get Protocol History: every revision of a protocol, oldest first
+#[ic_cdk::query]
+fn get_protocol_history(protocol_id: u64) -> Result<Vec<ProtocolRevision>, Message> {
+    get_emergency_protocol(protocol_id)?;
+    let ids = REVISIONS_BY_PROTOCOL.with(|index| index_ids(&index.borrow(), protocol_id, 0, u64::MAX));
+    Ok(PROTOCOL_REVISIONS.with(|revisions| {
+        let revisions = revisions.borrow();
+        ids.iter().filter_map(|id| revisions.get(id)).collect()
+    }))
+}
+
+// Fuel Management
+#[ic_cdk::update]
+fn update_fuel_inventory(
+    airstrip_id: u64,
+    fuel_type: String,
+    quantity: f64,
+    unit_price: f64,
+) -> Result<FuelInventory, Message> {
+    authorize(|role| role.manages(airstrip_id))?;
+
+    check_len("Fuel type", &fuel_type, MAX_NAME_LEN)?;
+    get_active_airstrip(airstrip_id)?;
+    if !quantity.is_finite() || quantity < 0.0 {
+        return Err(Message::InvalidPayload("Quantity must be a non-negative number".to_string()));
+    }
+    if !unit_price.is_finite() || unit_price < 0.0 {
+        return Err(Message::InvalidPayload("Unit price must be a non-negative number".to_string()));
+    }
+
+    let inventory_id = next_id();
+
+    let inventory = FuelInventory {
+        id: inventory_id,
+        airstrip_id,
+        fuel_type,
+        quantity,
+        unit_price,
+        last_updated: time(),
+        archived_at: None,
+    };
+
+    FUEL_INVENTORIES.with(|inventories| {
+        inventories.borrow_mut().insert(inventory_id, inventory.clone());
+    });
+    index_fuel_inventory(&inventory);
+
+    record_audit(AuditAction::Create, EntityKind::FuelInventory, inventory_id, None, Some(&inventory));
+
+    Ok(inventory)
+}
+
+// Revenue Tracking
+#[ic_cdk::update]
+fn record_revenue(
+    airstrip_id: u64,
+    source: String,
+    amount: f64,
+    description: String,
+) -> Result<Revenue, Message> {
+    authorize(|role| *role == Role::Finance)?;
+
+    check_len("Source", &source, MAX_NAME_LEN)?;
+    check_len("Description", &description, MAX_TEXT_LEN)?;
+    get_active_airstrip(airstrip_id)?;
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(Message::InvalidPayload("Amount must be a non-negative number".to_string()));
+    }
+
+    let revenue_id = next_id();
+
+    let revenue = Revenue {
+        id: revenue_id,
+        airstrip_id,
+        source,
+        amount,
+        transaction_date: time(),
+        description,
+        archived_at: None,
+    };
+
+    REVENUES.with(|revenues| {
+        revenues.borrow_mut().insert(revenue_id, revenue.clone());
+    });
+    index_revenue(&revenue);
+
+    record_audit(AuditAction::Create, EntityKind::Revenue, revenue_id, None, Some(&revenue));
+
+    Ok(revenue)
+}
+
+// Revenue Analysis
+#[ic_cdk::query]
+fn get_revenue_analysis(airstrip_id: u64, start_time: u64, end_time: u64) -> Result<HashMap<String, f64>, Message> {
+    let mut analysis = HashMap::new();
+    let mut total_revenue = 0.0;
+
+    let ids = REVENUE_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, start_time, end_time));
+    REVENUES.with(|revenues| {
+        let revenues = revenues.borrow();
+        for revenue in ids.iter().filter_map(|id| revenues.get(id)) {
+            *analysis.entry(revenue.source.clone()).or_insert(0.0) += revenue.amount;
+            total_revenue += revenue.amount;
+        }
+    });
+
+    analysis.insert("total".to_string(), total_revenue);
+    Ok(analysis)
+}
+
+// Expense Tracking
+#[ic_cdk::update]
+fn record_expense(
+    airstrip_id: u64,
+    category: String,
+    amount: f64,
+    description: String,
+) -> Result<Expense, Message> {
+    authorize(|role| *role == Role::Finance)?;
+
+    check_len("Category", &category, MAX_NAME_LEN)?;
+    check_len("Description", &description, MAX_TEXT_LEN)?;
+    get_active_airstrip(airstrip_id)?;
+    if !amount.is_finite() || amount < 0.0 {
+        return Err(Message::InvalidPayload("Amount must be a non-negative number".to_string()));
+    }
+
+    Ok(insert_expense(Expense {
+        airstrip_id,
+        category,
+        amount,
+        transaction_date: time(),
+        description,
+        ..Default::default()
+    }))
+}
+
+// Stores the expense under a new id
+fn insert_expense(mut expense: Expense) -> Expense {
+    expense.id = next_id();
+
+    EXPENSES.with(|expenses| {
+        expenses.borrow_mut().insert(expense.id, expense.clone());
+    });
+    index_expense(&expense);
+
+    record_audit(AuditAction::Create, EntityKind::Expense, expense.id, None, Some(&expense));
+
+    expense
+}
+
+// Expense Analysis, by category, with maintenance spend split into parts and labour
+#[ic_cdk::query]
+fn get_expense_analysis(airstrip_id: u64, start_time: u64, end_time: u64) -> Result<HashMap<String, f64>, Message> {
+    let mut analysis = HashMap::new();
+    let mut total_expenses = 0.0;
+
+    let ids = EXPENSES_BY_AIRSTRIP.with(|index| index_ids(&index.borrow(), airstrip_id, start_time, end_time));
+    EXPENSES.with(|expenses| {
+        let expenses = expenses.borrow();
+        for expense in ids.iter().filter_map(|id| expenses.get(id)) {
+            *analysis.entry(expense.category.clone()).or_insert(0.0) += expense.amount;
+            total_expenses += expense.amount;
+
+            let work_order = expense
+                .maintenance_id
+                .and_then(|id| MAINTENANCE_SCHEDULES.with(|schedules| schedules.borrow().get(&id)));
+            if let Some(work_order) = work_order {
+                *analysis.entry("maintenance_parts".to_string()).or_insert(0.0) += work_order.parts_cost();
+                *analysis.entry("maintenance_labour".to_string()).or_insert(0.0) += work_order.labour_cost();
+            }
+        }
+    });
+
+    analysis.insert("total".to_string(), total_expenses);
+    Ok(analysis)
+}
+
+// Pilot logbook
+
+// Complete a pilot's schedule once its flight has arrived, crediting the logbook
+#[ic_cdk::update]
+fn complete_pilot_schedule(schedule_id: u64) -> Result<Option<LogbookEntry>, Message> {
+    let schedule = match PILOT_SCHEDULES.with(|schedules| schedules.borrow().get(&schedule_id)) {
+        Some(schedule) => schedule,
+        None => return Err(Message::NotFound("Pilot schedule not found".to_string())),
+    };
+    let flight = match FLIGHTS.with(|flights| flights.borrow().get(&schedule.flight_id)) {
+        Some(flight) => flight,
+        None => return Err(Message::NotFound("Flight not found".to_string())),
+    };
+    authorize(|role| *role == Role::Dispatcher || role.manages(flight.airstrip_id))?;
+
+    if schedule.status != "scheduled" {
+        return Err(Message::Error(format!("Schedule is already {}", schedule.status)));
+    }
+    if !matches!(flight.status, FlightStatus::Arrived | FlightStatus::Completed) {
+        return Err(Message::Error("Flight has not arrived yet".to_string()));
+    }
+
+    Ok(complete_schedule(schedule, &flight))
+}
+
+// Marks the schedule completed and, for flight-deck crew, logs the flight
+fn complete_schedule(mut schedule: PilotSchedule, flight: &Flight) -> Option<LogbookEntry> {
+    let before = schedule.clone();
+    schedule.status = "completed".to_string();
+    PILOT_SCHEDULES.with(|schedules| {
+        schedules.borrow_mut().insert(schedule.id, schedule.clone());
+    });
+    record_audit(AuditAction::Update, EntityKind::PilotSchedule, schedule.id, Some(&before), Some(&schedule));
+
+    if !schedule.role.is_flight_crew() {
+        return None;
+    }
+
+    let entry_id = next_id();
+
+    let aircraft = AIRCRAFT.with(|aircraft| aircraft.borrow().get(&flight.aircraft_id)).unwrap_or_default();
+    let offset = solar_offset(flight.airstrip_id);
+    let entry = LogbookEntry {
+        id: entry_id,
+        pilot_id: schedule.pilot_id,
+        schedule_id: schedule.id,
+        flight_id: flight.id,
+        airstrip_id: flight.airstrip_id,
+        aircraft_id: flight.aircraft_id,
+        aircraft_type: aircraft.aircraft_type,
+        registration: aircraft.registration,
+        departure_time: flight.departure_time,
+        arrival_time: flight.arrival_time,
+        flight_minutes: flight.arrival_time.saturating_sub(flight.departure_time) / NANOS_PER_MINUTE,
+        night_minutes: night_time(flight.departure_time, flight.arrival_time, offset) / NANOS_PER_MINUTE,
+        night_takeoff: is_night(flight.departure_time, offset),
+        night_landing: is_night(flight.arrival_time, offset),
+        created_at: time(),
+        role: schedule.role,
+    };
+
+    LOGBOOK.with(|logbook| {
+        logbook.borrow_mut().insert(entry_id, entry.clone());
+    });
+    index_logbook_entry(&entry);
+
+    record_audit(AuditAction::Create, EntityKind::LogbookEntry, entry_id, None, Some(&entry));
+
+    Some(entry)
+}
+
+// Offset of local mean solar time from UTC at the airstrip; zero when its coordinates are unknown
+fn solar_offset(airstrip_id: u64) -> i64 {
+    let longitude = AIRSTRIPS
+        .with(|airstrips| airstrips.borrow().get(&airstrip_id))
+        .and_then(|airstrip| airstrip.coordinates)
+        .map_or(0.0, |coordinates| coordinates.longitude);
+    (longitude / 15.0 * NANOS_PER_HOUR as f64) as i64
+}
+
+fn local_time(timestamp: u64, offset: i64) -> u64 {
+    (timestamp as i128 + offset as i128).max(0) as u64
+}
+
+fn is_night(timestamp: u64, offset: i64) -> bool {
+    let hour = local_time(timestamp, offset) % NANOS_PER_DAY / NANOS_PER_HOUR;
+    !(NIGHT_END_HOUR..NIGHT_START_HOUR).contains(&hour)
+}
+
+// How much of [start, end) falls at night
+fn night_time(start: u64, end: u64, offset: i64) -> u64 {
+    let (start, end) = (local_time(start, offset), local_time(end, offset));
+    let mut total = 0;
+    let mut midnight = start / NANOS_PER_DAY * NANOS_PER_DAY;
+    while midnight < end {
+        total += overlap(start, end, midnight, midnight + NIGHT_END_HOUR * NANOS_PER_HOUR);
+        total += overlap(start, end, midnight + NIGHT_START_HOUR * NANOS_PER_HOUR, midnight + NANOS_PER_DAY);
+        midnight += NANOS_PER_DAY;
+    }
+    total
+}
+
+// Logbook entries whose arrival falls within [start, end], oldest first
+fn logbook_entries(pilot_id: u64, start: u64, end: u64) -> Vec<LogbookEntry> {
+    let ids = LOGBOOK_BY_PILOT.with(|index| index_ids(&index.borrow(), pilot_id, start, end));
+    LOGBOOK.with(|logbook| {
+        let logbook = logbook.borrow();
+        ids.iter().filter_map(|id| logbook.get(id)).collect()
+    })
+}
+
+// A pilot's logbook, filtered by aircraft type and arrival time
+#[ic_cdk::query]
+fn get_logbook(pilot_id: u64, filter: LogbookFilter, page: PageRequest) -> Result<Page<LogbookEntry>, Message> {
+    get_pilot(pilot_id)?;
+    let start = filter.start_time.unwrap_or(0);
+    let end = filter.end_time.unwrap_or(u64::MAX);
+    LOGBOOK.with(|logbook| {
+        let logbook = logbook.borrow();
+        LOGBOOK_BY_PILOT.with(|index| {
+            collect_page(&index.borrow(), IndexKey::span(pilot_id, start, end), &page, |key, _| {
+                logbook.get(&key.id).filter(|entry| {
+                    filter
+                        .aircraft_type
+                        .as_ref()
+                        .is_none_or(|aircraft_type| entry.aircraft_type.eq_ignore_ascii_case(aircraft_type))
+                })
+            })
+        })
+    })
+}
+
+// Lifetime totals from a pilot's logbook
+#[ic_cdk::query]
+fn get_logbook_totals(pilot_id: u64) -> Result<LogbookTotals, Message> {
+    get_pilot(pilot_id)?;
+    let mut totals = LogbookTotals {
+        pilot_id,
+        ..Default::default()
+    };
+    let mut by_type: HashMap<String, TypeTotals> = HashMap::new();
+
+    for entry in logbook_entries(pilot_id, 0, u64::MAX) {
+        totals.flights += 1;
+        totals.flight_minutes += entry.flight_minutes;
+        totals.night_minutes += entry.night_minutes;
+        if entry.role == CrewRole::PilotInCommand {
+            if entry.night_takeoff {
+                totals.night_takeoffs += 1;
+            } else {
+                totals.day_takeoffs += 1;
+            }
+            if entry.night_landing {
+                totals.night_landings += 1;
+            } else {
+                totals.day_landings += 1;
+            }
+        }
+        totals.last_flight_at = Some(entry.arrival_time);
+
+        let type_totals = by_type.entry(entry.aircraft_type.clone()).or_insert_with(|| TypeTotals {
+            aircraft_type: entry.aircraft_type.clone(),
+            flights: 0,
+            flight_minutes: 0,
+        });
+        type_totals.flights += 1;
+        type_totals.flight_minutes += entry.flight_minutes;
+    }
+
+    totals.by_aircraft_type = by_type.into_values().collect();
+    totals.by_aircraft_type.sort_by(|a, b| a.aircraft_type.cmp(&b.aircraft_type));
+    Ok(totals)
+}
+
+// Takeoffs and landings over the recency period ending at `as_of` (default now)
+#[ic_cdk::query]
+fn get_recency(pilot_id: u64, as_of: Option<u64>, aircraft_type: Option<String>) -> Result<Recency, Message> {
+    get_pilot(pilot_id)?;
+    let until = as_of.unwrap_or_else(time);
+    let since = until.saturating_sub(get_recency_policy().period_days * NANOS_PER_DAY);
+    Ok(recency(pilot_id, since, until, aircraft_type))
+}
+
+fn recency(pilot_id: u64, since: u64, until: u64, aircraft_type: Option<String>) -> Recency {
+    let entries: Vec<LogbookEntry> = logbook_entries(pilot_id, since, until.saturating_sub(1))
+        .into_iter()
+        .filter(|entry| entry.role == CrewRole::PilotInCommand)
+        .filter(|entry| {
+            aircraft_type
+                .as_ref()
+                .is_none_or(|aircraft_type| entry.aircraft_type.eq_ignore_ascii_case(aircraft_type))
+        })
+        .collect();
+
+    Recency {
+        pilot_id,
+        since,
+        until,
+        aircraft_type,
+        takeoffs: entries.len() as u64,
+        landings: entries.len() as u64,
+        night_takeoffs: entries.iter().filter(|entry| entry.night_takeoff).count() as u64,
+        night_landings: entries.iter().filter(|entry| entry.night_landing).count() as u64,
+    }
+}
+
+// Checks the pilot meets the recency policy for a duty on the flight starting at `start_time`
+fn check_recency(policy: &RecencyPolicy, pilot_id: u64, flight: &Flight, aircraft: &Aircraft, start_time: u64) -> Result<(), IneligibilityReason> {
+    let since = start_time.saturating_sub(policy.period_days * NANOS_PER_DAY);
+    let aircraft_type = policy.per_aircraft_type.then(|| aircraft.aircraft_type.clone());
+    let recent = recency(pilot_id, since, start_time, aircraft_type);
+
+    if recent.takeoffs < policy.min_takeoffs || recent.landings < policy.min_landings {
+        return Err(IneligibilityReason::NotRecent {
+            period_days: policy.period_days,
+            takeoffs: recent.takeoffs,
+            landings: recent.landings,
+            required_takeoffs: policy.min_takeoffs,
+            required_landings: policy.min_landings,
+        });
+    }
+
+    let offset = solar_offset(flight.airstrip_id);
+    let at_night = is_night(flight.departure_time, offset) || is_night(flight.arrival_time, offset);
+    if at_night && recent.night_landings < policy.min_night_landings {
+        return Err(IneligibilityReason::NotNightRecent {
+            period_days: policy.period_days,
+            night_landings: recent.night_landings,
+            required_night_landings: policy.min_night_landings,
+        });
+    }
+
+    Ok(())
+}
+
+// Current recency policy
+#[ic_cdk::query]
+fn get_recency_policy() -> RecencyPolicy {
+    RECENCY_POLICY.with(|policy| policy.borrow().get().clone())
+}
+
+// Replace the recency policy, including whether scheduling enforces it
+#[ic_cdk::update]
+fn set_recency_policy(policy: RecencyPolicy) -> Result<RecencyPolicy, Message> {
+    require_admin()?;
+    if policy.period_days == 0 {
+        return Err(Message::InvalidPayload("Recency period must be at least one day".to_string()));
+    }
+    let before = get_recency_policy();
+    RECENCY_POLICY
+        .with(|cell| cell.borrow_mut().set(policy.clone()))
+        .expect("Cannot store the recency policy");
+    record_audit(AuditAction::Update, EntityKind::RecencyPolicy, 0, Some(&before), Some(&policy));
+    Ok(policy)
+}
+
+// Pilot availability
+
+// Mark a pilot unavailable for a period. Schedules it clashes with are kept
+// and returned so dispatch can reassign them.
+#[ic_cdk::update]
+fn add_unavailability(payload: UnavailabilityPayload) -> Result<UnavailabilityRecorded, Message> {
+    authorize(|role| *role == Role::Dispatcher || *role == Role::Pilot(payload.pilot_id))?;
+    get_pilot(payload.pilot_id)?;
+    check_len("Note", &payload.note, MAX_TEXT_LEN)?;
+    if payload.end_time <= payload.start_time {
+        return Err(Message::InvalidPayload("End time must be after start time".to_string()));
+    }
+    if payload.end_time - payload.start_time > MAX_UNAVAILABILITY_DAYS * NANOS_PER_DAY {
+        return Err(Message::InvalidPayload(format!(
+            "An unavailability block cannot be longer than {} days",
+            MAX_UNAVAILABILITY_DAYS
+        )));
+    }
+
+    let block_id = next_id();
+
+    let block = Unavailability {
+        id: block_id,
+        pilot_id: payload.pilot_id,
+        start_time: payload.start_time,
+        end_time: payload.end_time,
+        reason: payload.reason,
+        note: payload.note,
+        created_at: time(),
+    };
+
+    UNAVAILABILITY.with(|blocks| blocks.borrow_mut().insert(block_id, block.clone()));
+    index_unavailability(&block);
 
-    static REVENUES: RefCell<StableBTreeMap<u64, Revenue, Memory>> =
-        RefCell::new(StableBTreeMap::init(
-            MEMORY_MANAGER.with(|m| m.borrow().get(MemoryId::new(17)))
-        ));
+    record_audit(AuditAction::Create, EntityKind::Unavailability, block_id, None, Some(&block));
+
+    let ids = SCHEDULES_BY_PILOT.with(|index| {
+        index_ids(
+            &index.borrow(),
+            block.pilot_id,
+            block.start_time.saturating_sub(MAX_DUTY_LENGTH),
+            block.end_time.saturating_sub(1),
+        )
+    });
+    let conflicts = PILOT_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        ids.iter()
+            .filter_map(|id| schedules.get(id))
+            .filter(|schedule| {
+                schedule.status == "scheduled"
+                    && schedule.start_time < block.end_time
+                    && schedule.end_time > block.start_time
+            })
+            .collect()
+    });
+
+    Ok(UnavailabilityRecorded { block, conflicts })
 }
 
-// Functions
+// Remove an unavailability block
+#[ic_cdk::update]
+fn remove_unavailability(block_id: u64) -> Result<Unavailability, Message> {
+    let block = match UNAVAILABILITY.with(|blocks| blocks.borrow().get(&block_id)) {
+        Some(block) => block,
+        None => return Err(Message::NotFound("Unavailability block not found".to_string())),
+    };
+    authorize(|role| *role == Role::Dispatcher || *role == Role::Pilot(block.pilot_id))?;
 
-// Create Airstrip
+    UNAVAILABILITY.with(|blocks| blocks.borrow_mut().remove(&block_id));
+    UNAVAILABILITY_BY_PILOT.with(|index| {
+        index
+            .borrow_mut()
+            .remove(&IndexKey::new(block.pilot_id, block.start_time, block.id))
+    });
+
+    record_audit(AuditAction::Delete, EntityKind::Unavailability, block_id, Some(&block), None);
+
+    Ok(block)
+}
+
+// A pilot's unavailability blocks that overlap [start_time, end_time)
+#[ic_cdk::query]
+fn get_unavailability(pilot_id: u64, start_time: u64, end_time: u64) -> Result<Vec<Unavailability>, Message> {
+    get_pilot(pilot_id)?;
+    Ok(unavailability_blocks(pilot_id, start_time, end_time))
+}
+
+fn unavailability_blocks(pilot_id: u64, start: u64, end: u64) -> Vec<Unavailability> {
+    let longest = MAX_UNAVAILABILITY_DAYS * NANOS_PER_DAY;
+    let ids = UNAVAILABILITY_BY_PILOT.with(|index| {
+        index_ids(&index.borrow(), pilot_id, start.saturating_sub(longest), end.saturating_sub(1))
+    });
+    UNAVAILABILITY.with(|blocks| {
+        let blocks = blocks.borrow();
+        ids.iter()
+            .filter_map(|id| blocks.get(id))
+            .filter(|block| block.start_time < end && block.end_time > start)
+            .collect()
+    })
+}
+
+// Replace a pilot's weekly availability; no windows means available at any time.
+// The windows are hard limits rather than preferences: schedule_pilot,
+// reschedule_pilot_schedule and auto_roster refuse duties outside them with
+// OutsideWeeklyAvailability.
 #[ic_cdk::update]
-fn create_airstrip(payload: CreateAirstripPayload) -> Result<Airstrip, Message> {
-    if payload.name.is_empty() || payload.contact.is_empty() || payload.email.is_empty() {
-        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+fn set_weekly_availability(pilot_id: u64, windows: Vec<AvailabilityWindow>) -> Result<WeeklyAvailability, Message> {
+    authorize(|role| *role == Role::Dispatcher || *role == Role::Pilot(pilot_id))?;
+    get_pilot(pilot_id)?;
+    if windows.len() > MAX_LIST_LEN {
+        return Err(Message::InvalidPayload(format!(
+            "Weekly availability cannot have more than {} windows",
+            MAX_LIST_LEN
+        )));
+    }
+    if windows
+        .iter()
+        .any(|window| window.start_minute >= window.end_minute || window.end_minute > MINUTES_PER_DAY)
+    {
+        return Err(Message::InvalidPayload(
+            "Each window must start before it ends and end by midnight".to_string(),
+        ));
+    }
+
+    let before = WEEKLY_AVAILABILITY.with(|availability| availability.borrow().get(&pilot_id));
+    let availability = WeeklyAvailability {
+        pilot_id,
+        windows,
+        updated_at: time(),
+    };
+    WEEKLY_AVAILABILITY.with(|weekly| {
+        let mut weekly = weekly.borrow_mut();
+        if availability.windows.is_empty() {
+            weekly.remove(&pilot_id);
+        } else {
+            weekly.insert(pilot_id, availability.clone());
+        }
+    });
+
+    record_audit(AuditAction::Update, EntityKind::WeeklyAvailability, pilot_id, before.as_ref(), Some(&availability));
+
+    Ok(availability)
+}
+
+// A pilot's weekly availability; empty windows when none is set
+#[ic_cdk::query]
+fn get_weekly_availability(pilot_id: u64) -> Result<WeeklyAvailability, Message> {
+    get_pilot(pilot_id)?;
+    Ok(WEEKLY_AVAILABILITY
+        .with(|availability| availability.borrow().get(&pilot_id))
+        .unwrap_or(WeeklyAvailability {
+            pilot_id,
+            ..Default::default()
+        }))
+}
+
+// Checks the duty misses every unavailability block and, when the pilot has a
+// weekly pattern, falls inside it at the flight's airstrip
+fn check_availability(pilot_id: u64, airstrip_id: u64, duty: (u64, u64)) -> Result<(), IneligibilityReason> {
+    if let Some(block) = unavailability_blocks(pilot_id, duty.0, duty.1).into_iter().next() {
+        return Err(IneligibilityReason::Unavailable {
+            block_id: block.id,
+            reason: block.reason,
+            until: block.end_time,
+        });
+    }
+
+    let weekly = WEEKLY_AVAILABILITY.with(|availability| availability.borrow().get(&pilot_id));
+    match weekly {
+        Some(weekly) if !weekly_covers(&weekly.windows, duty, solar_offset(airstrip_id)) => {
+            Err(IneligibilityReason::OutsideWeeklyAvailability)
+        }
+        _ => Ok(()),
+    }
+}
+
+// Whether the windows cover all of the duty in local time. Walks forward from
+// the duty start, each step jumping to the furthest end of a window that holds
+// the current minute.
+fn weekly_covers(windows: &[AvailabilityWindow], duty: (u64, u64), offset: i64) -> bool {
+    let (start, end) = (local_time(duty.0, offset), local_time(duty.1, offset));
+    let mut cursor = start;
+    while cursor < end {
+        let day = cursor / NANOS_PER_DAY;
+        let weekday = Weekday::of_day(day);
+        let minute = cursor % NANOS_PER_DAY / NANOS_PER_MINUTE;
+        let reach = windows
+            .iter()
+            .filter(|window| window.day == weekday && window.start_minute <= minute && minute < window.end_minute)
+            .map(|window| window.end_minute)
+            .max();
+        match reach {
+            Some(end_minute) => cursor = day * NANOS_PER_DAY + end_minute * NANOS_PER_MINUTE,
+            None => return false,
+        }
+    }
+    true
+}
+
+// Query functions for new features
+#[ic_cdk::query]
+fn get_pilot_schedule(pilot_id: u64, filter: PilotScheduleFilter, page: PageRequest) -> Result<Page<PilotSchedule>, Message> {
+    let start = filter.start_time.unwrap_or(0);
+    let end = filter.end_time.unwrap_or(u64::MAX);
+    PILOT_SCHEDULES.with(|schedules| {
+        let schedules = schedules.borrow();
+        SCHEDULES_BY_PILOT.with(|index| {
+            collect_page(&index.borrow(), IndexKey::span(pilot_id, start, end), &page, |key, _| {
+                schedules
+                    .get(&key.id)
+                    .filter(|schedule| filter.status.as_ref().is_none_or(|status| schedule.status == *status))
+            })
+        })
+    })
+}
+
+#[ic_cdk::query]
+fn get_emergency_protocols(airstrip_id: u64, filter: ProtocolFilter, page: PageRequest) -> Result<Page<EmergencyProtocol>, Message> {
+    EMERGENCY_PROTOCOLS.with(|protocols| {
+        let protocols = protocols.borrow();
+        PROTOCOLS_BY_AIRSTRIP.with(|index| {
+            collect_page(&index.borrow(), IndexKey::span(airstrip_id, 0, u64::MAX), &page, |key, _| {
+                protocols.get(&key.id).filter(|protocol| {
+                    (filter.include_archived || protocol.archived_at.is_none())
+                        && filter.protocol_type.as_ref().is_none_or(|kind| protocol.protocol_type == *kind)
+                })
+            })
+        })
+    })
+}
+
+#[ic_cdk::query]
+fn get_fuel_inventory(airstrip_id: u64, filter: FuelFilter, page: PageRequest) -> Result<Page<FuelInventory>, Message> {
+    let start = filter.start_time.unwrap_or(0);
+    let end = filter.end_time.unwrap_or(u64::MAX);
+    FUEL_INVENTORIES.with(|inventory| {
+        let inventory = inventory.borrow();
+        FUEL_BY_AIRSTRIP.with(|index| {
+            collect_page(&index.borrow(), IndexKey::span(airstrip_id, start, end), &page, |key, _| {
+                inventory.get(&key.id).filter(|inv| {
+                    (filter.include_archived || inv.archived_at.is_none())
+                        && filter.fuel_type.as_ref().is_none_or(|fuel_type| inv.fuel_type == *fuel_type)
+                })
+            })
+        })
+    })
+}
+
+// Exporting the candid interface
+ic_cdk::export_candid!();
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use system::{set_caller, set_controller};
+
+    const HOUR: u64 = NANOS_PER_HOUR;
+    const DAY: u64 = NANOS_PER_DAY;
+
+    // 2023-01-01 and 2024-01-01 as days since the epoch
+    const JAN_1_2023: u64 = 19_358;
+    const JAN_1_2024: u64 = 19_723;
+
+    // A distinct principal per n; one-byte principals would include the anonymous one
+    fn principal(n: u8) -> Principal {
+        Principal::from_slice(&[n; 10])
+    }
+
+    // Calls from here on come from a controller, who may do anything
+    fn as_admin() {
+        set_controller(principal(1));
+        set_caller(principal(1));
+    }
+
+    // Records are created through the endpoints so ids and indexes come out as
+    // they would in the canister. Callers set up access with as_admin first.
+
+    fn airstrip_payload() -> CreateAirstripPayload {
+        CreateAirstripPayload {
+            name: "Wilson".to_string(),
+            location: "Nairobi".to_string(),
+            contact: "+254 20 000 000".to_string(),
+            email: "ops@example.com".to_string(),
+            runway_length: 1_800,
+            ..Default::default()
+        }
+    }
+
+    fn new_airstrip(payload: CreateAirstripPayload) -> Airstrip {
+        create_airstrip(payload).unwrap()
+    }
+
+    fn new_runway(airstrip_id: u64, designator: &str, length: u64) -> Runway {
+        add_runway(AddRunwayPayload {
+            airstrip_id,
+            designator: designator.to_string(),
+            width: 30,
+            declared_distances: DeclaredDistances { tora: length, toda: length, asda: length, lda: length },
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn aircraft_payload(registration: &str) -> RegisterAircraftPayload {
+        RegisterAircraftPayload {
+            registration: registration.to_string(),
+            aircraft_type: "C208".to_string(),
+            mtow: 3_969,
+            seat_count: 12,
+            takeoff_distance_required: 700,
+            landing_distance_required: 500,
+            fuel_type: "Jet A-1".to_string(),
+            operator: "Safari Air".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn new_aircraft(payload: RegisterAircraftPayload) -> Aircraft {
+        register_aircraft(payload).unwrap()
+    }
+
+    // A commercial pilot rated on the C208, licensed and fit well past any test date
+    fn pilot_payload(name: &str) -> RegisterPilotPayload {
+        RegisterPilotPayload {
+            name: name.to_string(),
+            license_number: format!("CPL-{}", name),
+            experience_years: 5,
+            license_class: LicenseClass::Commercial,
+            license_expiry: u64::MAX,
+            medical_class: MedicalClass::Class1,
+            medical_expiry: u64::MAX,
+            type_ratings: vec!["C208".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn new_pilot(payload: RegisterPilotPayload) -> Pilot {
+        register_pilot(payload).unwrap()
+    }
+
+    // An hour-long passenger departure needing no stand
+    fn flight_payload(airstrip_id: u64, aircraft_id: u64, departure_time: u64) -> ScheduleFlightPayload {
+        ScheduleFlightPayload {
+            airstrip_id,
+            flight_number: "KQ100".to_string(),
+            destination: "Mombasa".to_string(),
+            departure_time,
+            arrival_time: departure_time + HOUR,
+            aircraft_id,
+            ..Default::default()
+        }
+    }
+
+    fn new_flight(payload: ScheduleFlightPayload) -> Flight {
+        schedule_flight(payload).unwrap().flight
+    }
+
+    // Takes a scheduled flight through its lifecycle to Completed
+    fn fly(flight_id: u64) -> Flight {
+        let mut flight = None;
+        for status in [
+            FlightStatus::Boarding,
+            FlightStatus::Departed,
+            FlightStatus::Airborne,
+            FlightStatus::Arrived,
+            FlightStatus::Completed,
+        ] {
+            flight = Some(change_flight_status(flight_id, status).unwrap().0);
+        }
+        flight.unwrap()
+    }
+
+    mod access {
+        use super::*;
+
+        fn add_runway_to(airstrip_id: u64) -> Result<Runway, Message> {
+            add_runway(AddRunwayPayload {
+                airstrip_id,
+                designator: "09".to_string(),
+                width: 30,
+                declared_distances: DeclaredDistances { tora: 1_800, toda: 1_800, asda: 1_800, lda: 1_800 },
+                ..Default::default()
+            })
+        }
+
+        #[test]
+        fn anonymous_callers_are_turned_away() {
+            set_caller(Principal::anonymous());
+            assert!(matches!(create_airstrip(airstrip_payload()), Err(Message::Unauthorized(_))));
+            assert!(matches!(list_role_assignments(PageRequest::default()), Err(Message::Unauthorized(_))));
+        }
+
+        #[test]
+        fn a_manager_acts_only_on_their_own_airstrip() {
+            as_admin();
+            let own = new_airstrip(airstrip_payload());
+            let other = new_airstrip(airstrip_payload());
+            grant_role(principal(2), Role::AirstripManager(own.id)).unwrap();
+
+            set_caller(principal(2));
+            assert!(add_runway_to(own.id).is_ok());
+            assert!(matches!(add_runway_to(other.id), Err(Message::Unauthorized(_))));
+            assert!(matches!(create_airstrip(airstrip_payload()), Err(Message::Unauthorized(_))));
+            assert!(matches!(grant_role(principal(2), Role::AirstripManager(other.id)), Err(Message::Unauthorized(_))));
+        }
+
+        #[test]
+        fn a_revoked_role_stops_working() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let manager = Role::AirstripManager(airstrip.id);
+            assert_eq!(grant_role(principal(2), manager).unwrap().roles, vec![manager]);
+            assert!(matches!(grant_role(principal(2), manager), Err(Message::InvalidPayload(_))));
+            assert!(matches!(grant_role(Principal::anonymous(), manager), Err(Message::InvalidPayload(_))));
+
+            set_caller(principal(2));
+            assert_eq!(get_my_roles().roles, vec![manager]);
+            assert!(add_runway_to(airstrip.id).is_ok());
+
+            as_admin();
+            assert!(revoke_role(principal(2), manager).unwrap().roles.is_empty());
+            assert!(matches!(revoke_role(principal(2), manager), Err(Message::NotFound(_))));
+
+            set_caller(principal(2));
+            assert!(get_my_roles().roles.is_empty());
+            assert!(matches!(add_runway_to(airstrip.id), Err(Message::Unauthorized(_))));
+        }
+    }
+
+    mod airstrips {
+        use super::*;
+
+        #[test]
+        fn archiving_releases_flights_and_resumes_from_its_cursor() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let flight = new_flight(flight_payload(airstrip.id, aircraft.id, 10 * HOUR));
+            let pilot = new_pilot(pilot_payload("Amina"));
+            let schedule = schedule_pilot(pilot.id, flight.id, 9 * HOUR, 12 * HOUR, CrewRole::PilotInCommand).unwrap();
+            for _ in 0..MAX_ARCHIVE_RECORDS {
+                update_fuel_inventory(airstrip.id, "Jet A-1".to_string(), 1_000.0, 1.25).unwrap();
+            }
+
+            let first = archive_airstrip(airstrip.id, None).unwrap();
+            assert_eq!(first.archived, MAX_ARCHIVE_RECORDS as u64);
+            assert!(first.airstrip.archived_at.is_none());
+            let cursor = first.resume_from.unwrap();
+            assert_eq!(cursor.entity, EntityKind::FuelInventory);
+
+            let flight = FLIGHTS.with(|flights| flights.borrow().get(&flight.id)).unwrap();
+            assert!(flight.archived_at.is_some());
+            let slot = SLOT_RESERVATIONS.with(|slots| slots.borrow().get(&flight.slot_id.unwrap())).unwrap();
+            assert!(slot.released_at.is_some());
+            let schedule = PILOT_SCHEDULES.with(|schedules| schedules.borrow().get(&schedule.id)).unwrap();
+            assert_eq!(schedule.status, "cancelled");
+
+            let second = archive_airstrip(airstrip.id, Some(cursor)).unwrap();
+            assert_eq!(second.archived, 1);
+            assert!(second.resume_from.is_none());
+            assert!(second.airstrip.archived_at.is_some());
+        }
+
+        #[test]
+        fn fuel_and_revenue_need_a_live_airstrip_and_sensible_amounts() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let fuel = |quantity: f64, unit_price: f64| {
+                update_fuel_inventory(airstrip.id, "Jet A-1".to_string(), quantity, unit_price)
+            };
+            assert!(fuel(1_000.0, 1.25).is_ok());
+            assert!(matches!(fuel(-1.0, 1.25), Err(Message::InvalidPayload(_))));
+            assert!(matches!(fuel(1_000.0, f64::NAN), Err(Message::InvalidPayload(_))));
+            let revenue = |amount: f64| record_revenue(airstrip.id, "landing_fees".to_string(), amount, String::new());
+            assert!(revenue(320.0).is_ok());
+            assert!(matches!(revenue(f64::INFINITY), Err(Message::InvalidPayload(_))));
+
+            archive_airstrip(airstrip.id, None).unwrap();
+            assert!(fuel(1_000.0, 1.25).is_err());
+            assert!(revenue(320.0).is_err());
+        }
+
+        #[test]
+        fn a_search_radius_must_be_positive() {
+            for radius_km in [0.0, -5.0, f64::NAN] {
+                assert!(matches!(find_airstrips_within(-1.3, 36.8, radius_km), Err(Message::InvalidPayload(_))));
+            }
+            assert!(find_airstrips_within(-1.3, 36.8, 50.0).is_ok());
+        }
+
+        #[test]
+        fn a_runway_in_use_cannot_be_removed() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let other = new_airstrip(airstrip_payload());
+            let used = new_runway(airstrip.id, "09", 1_800);
+            let spare = new_runway(airstrip.id, "27", 1_800);
+            new_runway(other.id, "09", 1_800);
+            let aircraft = new_aircraft(aircraft_payload("5Y-RWY"));
+            new_flight(ScheduleFlightPayload {
+                runway_id: Some(used.id),
+                ..flight_payload(airstrip.id, aircraft.id, JAN_1_2024)
+            });
+
+            let listed = list_runways(airstrip.id, PageRequest::default()).unwrap();
+            assert_eq!(listed.items.len(), 2);
+            assert!(remove_runway(used.id).is_err());
+            remove_runway(spare.id).unwrap();
+            let listed = list_runways(airstrip.id, PageRequest::default()).unwrap();
+            assert_eq!(listed.items.iter().map(|r| r.id).collect::<Vec<_>>(), vec![used.id]);
+        }
+    }
+
+    mod flights {
+        use super::*;
+
+        #[test]
+        fn only_lifecycle_moves_are_allowed() {
+            use FlightStatus::*;
+            let all = [Scheduled, Boarding, Departed, Airborne, Arrived, Delayed, Diverted, Cancelled, Completed];
+            let legal = [
+                (Scheduled, Boarding),
+                (Scheduled, Delayed),
+                (Scheduled, Cancelled),
+                (Delayed, Scheduled),
+                (Delayed, Boarding),
+                (Delayed, Cancelled),
+                (Boarding, Departed),
+                (Boarding, Delayed),
+                (Boarding, Cancelled),
+                (Departed, Airborne),
+                (Airborne, Arrived),
+                (Airborne, Diverted),
+                (Diverted, Arrived),
+                (Arrived, Completed),
+            ];
+            for from in all {
+                for to in all {
+                    assert_eq!(from.can_transition_to(to), legal.contains(&(from, to)), "{:?} -> {:?}", from, to);
+                }
+            }
+        }
+
+        #[test]
+        fn an_illegal_status_change_is_rejected() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let flight = new_flight(flight_payload(airstrip.id, aircraft.id, 10 * HOUR));
+
+            assert!(matches!(change_flight_status(flight.id, FlightStatus::Airborne), Err(Message::InvalidPayload(_))));
+            let unchanged = FLIGHTS.with(|flights| flights.borrow().get(&flight.id)).unwrap();
+            assert_eq!(unchanged.status, FlightStatus::Scheduled);
+
+            let completed = fly(flight.id);
+            assert_eq!(completed.status, FlightStatus::Completed);
+            assert!(matches!(change_flight_status(flight.id, FlightStatus::Cancelled), Err(Message::InvalidPayload(_))));
+        }
+
+        #[test]
+        fn a_move_the_crew_cannot_follow_is_refused() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let first = new_aircraft(aircraft_payload("5Y-ABC"));
+            let second = new_aircraft(aircraft_payload("5Y-DEF"));
+            let pilot = new_pilot(pilot_payload("Amina"));
+            let flight = new_flight(flight_payload(airstrip.id, first.id, 10 * HOUR));
+            let schedule = schedule_pilot(pilot.id, flight.id, 9 * HOUR, 12 * HOUR, CrewRole::PilotInCommand).unwrap();
+            let later = new_flight(flight_payload(airstrip.id, second.id, DAY + 10 * HOUR));
+            schedule_pilot(pilot.id, later.id, DAY + 9 * HOUR, DAY + 12 * HOUR, CrewRole::PilotInCommand).unwrap();
+
+            let clash = RescheduleFlightPayload { departure_time: DAY + 10 * HOUR, arrival_time: DAY + 11 * HOUR };
+            match reschedule_flight(flight.id, clash) {
+                Err(Message::Error(detail)) => assert!(detail.contains(&schedule.id.to_string())),
+                _ => panic!("the move should have been refused"),
+            }
+            let unchanged = FLIGHTS.with(|flights| flights.borrow().get(&flight.id)).unwrap();
+            assert_eq!(unchanged.departure_time, 10 * HOUR);
+            let kept = PILOT_SCHEDULES.with(|schedules| schedules.borrow().get(&schedule.id)).unwrap();
+            assert_eq!((kept.status.as_str(), kept.start_time), ("scheduled", 9 * HOUR));
+
+            let later_that_day = RescheduleFlightPayload { departure_time: 14 * HOUR, arrival_time: 15 * HOUR };
+            let report = reschedule_flight(flight.id, later_that_day).unwrap();
+            assert!(report.affected.iter().any(|record| record.id == schedule.id));
+            let moved = PILOT_SCHEDULES.with(|schedules| schedules.borrow().get(&schedule.id)).unwrap();
+            assert_eq!((moved.start_time, moved.end_time), (13 * HOUR, 16 * HOUR));
+        }
+    }
+
+    mod pagination {
+        use super::*;
+
+        fn page(limit: u32, cursor: Option<String>, order: SortOrder) -> PageRequest {
+            PageRequest { limit: Some(limit), cursor, order }
+        }
+
+        // Airstrip ids page by page until the cursor runs out
+        fn every_page(limit: u32, order: SortOrder) -> Vec<Vec<u64>> {
+            let mut pages = Vec::new();
+            let mut cursor = None;
+            loop {
+                let found = list_airstrips(false, page(limit, cursor, order)).unwrap();
+                pages.push(found.items.iter().map(|airstrip| airstrip.id).collect());
+                match found.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => return pages,
+                }
+            }
+        }
+
+        #[test]
+        fn cursors_walk_every_record_once_in_either_order() {
+            as_admin();
+            let ids: Vec<u64> = (0..5).map(|_| new_airstrip(airstrip_payload()).id).collect();
+
+            let pages = every_page(2, SortOrder::Ascending);
+            assert_eq!(pages, vec![ids[0..2].to_vec(), ids[2..4].to_vec(), ids[4..].to_vec()]);
+
+            let reversed: Vec<u64> = ids.iter().rev().copied().collect();
+            let pages = every_page(2, SortOrder::Descending);
+            assert_eq!(pages, vec![reversed[0..2].to_vec(), reversed[2..4].to_vec(), reversed[4..].to_vec()]);
+        }
+
+        #[test]
+        fn cursors_follow_index_keys() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            // Scheduled out of order: the index keeps them by movement time
+            let late = new_flight(flight_payload(airstrip.id, aircraft.id, 12 * HOUR));
+            let early = new_flight(flight_payload(airstrip.id, aircraft.id, 8 * HOUR));
+            let filter = FlightFilter { airstrip_id: Some(airstrip.id), ..Default::default() };
+
+            let first = list_flights(filter.clone(), page(1, None, SortOrder::Ascending)).unwrap();
+            assert_eq!(first.items[0].id, early.id);
+            let second = list_flights(filter, page(1, first.next_cursor, SortOrder::Ascending)).unwrap();
+            assert_eq!(second.items[0].id, late.id);
+            assert!(second.next_cursor.is_none());
+        }
+
+        #[test]
+        fn role_assignments_page_by_principal() {
+            as_admin();
+            for n in 2..5 {
+                grant_role(principal(n), Role::Dispatcher).unwrap();
+            }
+            let first = list_role_assignments(page(2, None, SortOrder::Ascending)).unwrap();
+            assert_eq!(first.items.len(), 2);
+            let rest = list_role_assignments(page(2, first.next_cursor, SortOrder::Ascending)).unwrap();
+            assert_eq!(rest.items.len(), 1);
+            assert_eq!(rest.items[0].principal, principal(4));
+            assert!(rest.next_cursor.is_none());
+        }
+
+        #[test]
+        fn page_sizes_are_clamped() {
+            assert_eq!(PageRequest::default().page_size(), DEFAULT_PAGE_SIZE as usize);
+            assert_eq!(page(0, None, SortOrder::Ascending).page_size(), 1);
+            assert_eq!(page(u32::MAX, None, SortOrder::Ascending).page_size(), MAX_PAGE_SIZE as usize);
+
+            as_admin();
+            for _ in 0..=MAX_PAGE_SIZE {
+                new_airstrip(airstrip_payload());
+            }
+            let found = list_airstrips(false, page(u32::MAX, None, SortOrder::Ascending)).unwrap();
+            assert_eq!(found.items.len(), MAX_PAGE_SIZE as usize);
+            assert!(found.next_cursor.is_some());
+        }
+
+        #[test]
+        fn malformed_cursors_are_rejected() {
+            for cursor in ["", "12", "1.zz", "-1.2", "1.2.3"] {
+                let request = page(10, Some(cursor.to_string()), SortOrder::Ascending);
+                assert!(
+                    matches!(list_airstrips(false, request), Err(Message::InvalidPayload(_))),
+                    "cursor {:?} was accepted",
+                    cursor
+                );
+            }
+            let request = page(10, Some("not a principal".to_string()), SortOrder::Ascending);
+            as_admin();
+            assert!(matches!(list_role_assignments(request), Err(Message::InvalidPayload(_))));
+        }
+    }
+
+    mod records {
+        use super::*;
+        use candid::Encode;
+
+        // A record as the baseline canister stored it: bare Candid, no envelope
+        fn baseline_record<T: candid::CandidType>(value: &T) -> Vec<u8> {
+            Encode!(value).unwrap()
+        }
+
+        // A record written under schema version 1: the envelope around Candid
+        fn candid_v1_record<T: candid::CandidType>(value: &T) -> Vec<u8> {
+            let mut bytes = vec![RECORD_MAGIC, 1];
+            bytes.extend(Encode!(value).unwrap());
+            bytes
+        }
+
+        // Records of the types the original canister stored, in its bare Candid layouts
+
+        #[derive(candid::CandidType)]
+        struct BaselineAirstrip {
+            id: u64,
+            name: String,
+            location: String,
+            contact: String,
+            email: String,
+            runway_length: u64,
+            capacity: u64,
+            created_at: u64,
+        }
+
+        #[derive(candid::CandidType)]
+        struct BaselineFlight {
+            id: u64,
+            airstrip_id: u64,
+            flight_number: String,
+            destination: String,
+            departure_time: u64,
+            arrival_time: u64,
+            status: String,
+        }
+
+        #[derive(candid::CandidType)]
+        struct BaselinePilot {
+            id: u64,
+            name: String,
+            license_number: String,
+            experience_years: u64,
+            contact: String,
+            email: String,
+        }
+
+        #[derive(candid::CandidType)]
+        struct BaselinePilotSchedule {
+            id: u64,
+            pilot_id: u64,
+            flight_id: u64,
+            start_time: u64,
+            end_time: u64,
+            status: String,
+        }
+
+        #[derive(candid::CandidType)]
+        struct BaselineMaintenance {
+            id: u64,
+            airstrip_id: u64,
+            date: u64,
+            description: String,
+            status: String,
+        }
+
+        #[derive(candid::CandidType)]
+        struct BaselineEmergencyProtocol {
+            id: u64,
+            airstrip_id: u64,
+            protocol_type: String,
+            description: String,
+            contact_numbers: Vec<String>,
+            evacuation_routes: Vec<String>,
+            created_at: u64,
+        }
+
+        #[derive(candid::CandidType)]
+        struct BaselineFuelInventory {
+            id: u64,
+            airstrip_id: u64,
+            fuel_type: String,
+            quantity: f64,
+            unit_price: f64,
+            last_updated: u64,
+        }
+
+        #[derive(candid::CandidType)]
+        struct BaselineRevenue {
+            id: u64,
+            airstrip_id: u64,
+            source: String,
+            amount: f64,
+            transaction_date: u64,
+            description: String,
+        }
+
+        // Flight as schema version 1 stored it, before flight categories
+        #[derive(candid::CandidType)]
+        struct FlightV1Record {
+            id: u64,
+            airstrip_id: u64,
+            flight_number: String,
+            destination: String,
+            departure_time: u64,
+            arrival_time: u64,
+            aircraft_id: u64,
+            runway_id: Option<u64>,
+            movement: MovementType,
+            ground_time_minutes: u64,
+            slot_id: Option<u64>,
+            stand_assignment_id: Option<u64>,
+            status: FlightStatus,
+            status_history: Vec<FlightStatusChange>,
+            archived_at: Option<u64>,
+        }
+
+        // Aircraft as schema version 1 stored it
+        #[derive(candid::CandidType)]
+        struct AircraftV1Record {
+            id: u64,
+            registration: String,
+            aircraft_type: String,
+            mtow: u64,
+            seat_count: u64,
+            takeoff_distance_required: u64,
+            landing_distance_required: u64,
+            fuel_type: String,
+            operator: String,
+            created_at: u64,
+        }
+
+        // Maintenance as schema version 1 stored it, once records could be archived
+        #[derive(candid::CandidType)]
+        struct MaintenanceV1 {
+            id: u64,
+            airstrip_id: u64,
+            date: u64,
+            description: String,
+            status: String,
+            archived_at: Option<u64>,
+        }
+
+        #[test]
+        fn baseline_airstrip_decodes_without_coordinates_or_limits() {
+            let old = BaselineAirstrip {
+                id: 1,
+                name: "Wilson".to_string(),
+                location: "Nairobi".to_string(),
+                contact: "+254 20 000 000".to_string(),
+                email: "ops@example.com".to_string(),
+                runway_length: 1_500,
+                capacity: 12,
+                created_at: 5 * DAY,
+            };
+            let airstrip: Airstrip = decode_record(&baseline_record(&old));
+            assert_eq!(airstrip.id, 1);
+            assert_eq!(airstrip.name, "Wilson");
+            assert_eq!(airstrip.location, "Nairobi");
+            assert_eq!(airstrip.email, "ops@example.com");
+            assert_eq!(airstrip.runway_length, 1_500);
+            assert_eq!(airstrip.capacity, 12);
+            assert_eq!(airstrip.created_at, 5 * DAY);
+            assert!(airstrip.coordinates.is_none());
+            assert_eq!(airstrip.movements_per_hour, 0);
+            assert_eq!(airstrip.archived_at, None);
+        }
+
+        #[test]
+        fn baseline_flight_decodes_as_a_passenger_departure() {
+            let old = BaselineFlight {
+                id: 2,
+                airstrip_id: 1,
+                flight_number: "KQ100".to_string(),
+                destination: "Mombasa".to_string(),
+                departure_time: 10 * HOUR,
+                arrival_time: 11 * HOUR,
+                status: "delayed".to_string(),
+            };
+            let flight: Flight = decode_record(&baseline_record(&old));
+            assert_eq!(flight.id, 2);
+            assert_eq!(flight.flight_number, "KQ100");
+            assert_eq!(flight.destination, "Mombasa");
+            assert_eq!(flight.departure_time, 10 * HOUR);
+            assert_eq!(flight.arrival_time, 11 * HOUR);
+            assert_eq!(flight.status, FlightStatus::Delayed);
+            assert!(matches!(flight.movement, MovementType::Departure));
+            assert!(matches!(flight.category, FlightCategory::Passenger));
+            assert_eq!(flight.aircraft_id, 0);
+            assert_eq!(flight.runway_id, None);
+            assert!(flight.status_history.is_empty());
+        }
+
+        #[test]
+        fn version_1_flight_decodes_as_a_passenger_flight() {
+            let old = FlightV1Record {
+                id: 3,
+                airstrip_id: 1,
+                flight_number: "KQ101".to_string(),
+                destination: "Lokichogio".to_string(),
+                departure_time: 1_000,
+                arrival_time: 2_000,
+                aircraft_id: 5,
+                runway_id: Some(2),
+                movement: MovementType::Arrival,
+                ground_time_minutes: 45,
+                slot_id: Some(8),
+                stand_assignment_id: None,
+                status: FlightStatus::Boarding,
+                status_history: vec![FlightStatusChange {
+                    status: FlightStatus::Boarding,
+                    changed_at: 900,
+                }],
+                archived_at: None,
+            };
+            let flight: Flight = decode_record(&candid_v1_record(&old));
+            assert_eq!(flight.flight_number, "KQ101");
+            assert_eq!((flight.aircraft_id, flight.runway_id, flight.slot_id), (5, Some(2), Some(8)));
+            assert_eq!(flight.movement, MovementType::Arrival);
+            assert_eq!(flight.ground_time_minutes, 45);
+            assert_eq!(flight.status, FlightStatus::Boarding);
+            assert_eq!(flight.status_history.len(), 1);
+            assert_eq!(flight.category, FlightCategory::Passenger);
+        }
+
+        #[test]
+        fn version_1_aircraft_decodes_with_derived_crew_and_no_history() {
+            let old = AircraftV1Record {
+                id: 5,
+                registration: "5Y-ABC".to_string(),
+                aircraft_type: "C208".to_string(),
+                mtow: 3_969,
+                seat_count: 12,
+                takeoff_distance_required: 700,
+                landing_distance_required: 500,
+                fuel_type: "Jet A-1".to_string(),
+                operator: "Safari Air".to_string(),
+                created_at: 42,
+            };
+            let aircraft: Aircraft = decode_record(&candid_v1_record(&old));
+            assert_eq!(aircraft.registration, "5Y-ABC");
+            assert_eq!((aircraft.mtow, aircraft.seat_count), (3_969, 12));
+            assert_eq!(aircraft.created_at, 42);
+            assert_eq!(aircraft.min_flight_crew, 0);
+            assert_eq!(aircraft.flight_crew(), 1);
+            assert_eq!((aircraft.airframe_minutes, aircraft.landing_cycles), (0, 0));
+            assert!(aircraft.grounded.is_none());
+            assert!(aircraft.inspections.is_empty());
+        }
+
+        #[test]
+        fn candid_era_pilot_decodes_without_licence_details() {
+            let old = BaselinePilot {
+                id: 7,
+                name: "Amina Njeri".to_string(),
+                license_number: "CPL-1234".to_string(),
+                experience_years: 9,
+                contact: "+254 700 000 000".to_string(),
+                email: "amina@example.com".to_string(),
+            };
+            for bytes in [baseline_record(&old), candid_v1_record(&old)] {
+                let pilot: Pilot = decode_record(&bytes);
+                assert_eq!(pilot.id, 7);
+                assert_eq!(pilot.name, "Amina Njeri");
+                assert_eq!(pilot.license_number, "CPL-1234");
+                assert_eq!(pilot.experience_years, 9);
+                assert_eq!(pilot.email, "amina@example.com");
+                assert_eq!(pilot.license_class, LicenseClass::Student);
+                assert_eq!(pilot.license_expiry, None);
+                assert_eq!(pilot.medical_class, MedicalClass::Class3);
+                assert!(pilot.type_ratings.is_empty());
+                assert!(pilot.crew_roles.is_empty());
+            }
+        }
+
+        #[test]
+        fn candid_era_schedule_decodes_as_pilot_in_command() {
+            let old = BaselinePilotSchedule {
+                id: 11,
+                pilot_id: 7,
+                flight_id: 3,
+                start_time: 100,
+                end_time: 200,
+                status: "completed".to_string(),
+            };
+            for bytes in [baseline_record(&old), candid_v1_record(&old)] {
+                let schedule: PilotSchedule = decode_record(&bytes);
+                assert_eq!((schedule.id, schedule.pilot_id, schedule.flight_id), (11, 7, 3));
+                assert_eq!((schedule.start_time, schedule.end_time), (100, 200));
+                assert_eq!(schedule.status, "completed");
+                assert_eq!(schedule.role, CrewRole::PilotInCommand);
+            }
+        }
+
+        #[test]
+        fn compact_records_fill_fields_added_after_them() {
+            // A version 2 schedule had no role; MessagePack stores fields by position
+            let old = (11u64, 7u64, 3u64, 100u64, 200u64, "scheduled".to_string());
+            let mut bytes = vec![RECORD_MAGIC, FIRST_COMPACT_VERSION];
+            rmp_serde::encode::write(&mut bytes, &old).unwrap();
+            let schedule: PilotSchedule = decode_record(&bytes);
+            assert_eq!(schedule.id, 11);
+            assert_eq!(schedule.status, "scheduled");
+            assert_eq!(schedule.role, CrewRole::PilotInCommand);
+        }
+
+        #[test]
+        fn candid_era_maintenance_decodes_without_a_window() {
+            let old = BaselineMaintenance {
+                id: 3,
+                airstrip_id: 1,
+                date: 40 * DAY,
+                description: "grade the strip".to_string(),
+                status: "completed".to_string(),
+            };
+            let maintenance: MaintenanceSchedule = decode_record(&baseline_record(&old));
+            assert_eq!(maintenance.id, 3);
+            assert_eq!(maintenance.date, 40 * DAY);
+            assert_eq!(maintenance.description, "grade the strip");
+            assert_eq!(maintenance.status, MaintenanceStatus::Completed);
+            assert_eq!(maintenance.end_time, 0);
+            assert_eq!(maintenance.impact, MaintenanceImpact::default());
+            assert_eq!(maintenance.window(), (40 * DAY, 41 * DAY));
+
+            let old = MaintenanceV1 {
+                id: 4,
+                airstrip_id: 1,
+                date: 40 * DAY,
+                description: "paint markings".to_string(),
+                status: "pending".to_string(),
+                archived_at: Some(50 * DAY),
+            };
+            let maintenance: MaintenanceSchedule = decode_record(&candid_v1_record(&old));
+            assert_eq!(maintenance.id, 4);
+            assert_eq!(maintenance.status, MaintenanceStatus::Scheduled);
+            assert_eq!(maintenance.archived_at, Some(50 * DAY));
+        }
+
+        #[test]
+        fn version_5_maintenance_keeps_its_status_enum() {
+            let current = MaintenanceSchedule {
+                id: 5,
+                airstrip_id: 1,
+                date: 40 * DAY,
+                end_time: 42 * DAY,
+                status: MaintenanceStatus::InProgress,
+                ..Default::default()
+            };
+            let mut bytes = vec![RECORD_MAGIC, 5];
+            rmp_serde::encode::write(&mut bytes, &current).unwrap();
+            let maintenance: MaintenanceSchedule = decode_record(&bytes);
+            assert_eq!(maintenance.status, MaintenanceStatus::InProgress);
+            assert_eq!(maintenance.end_time, 42 * DAY);
+        }
+
+        #[test]
+        fn baseline_emergency_protocol_decodes_as_its_first_revision() {
+            let old = BaselineEmergencyProtocol {
+                id: 6,
+                airstrip_id: 1,
+                protocol_type: " Fire ".to_string(),
+                description: "Foam tender to the threshold".to_string(),
+                contact_numbers: vec!["999".to_string()],
+                evacuation_routes: vec!["North gate".to_string()],
+                created_at: 3 * DAY,
+            };
+            let protocol: EmergencyProtocol = decode_record(&baseline_record(&old));
+            assert_eq!(protocol.id, 6);
+            assert_eq!(protocol.protocol_type, ProtocolType::Fire);
+            assert_eq!(protocol.description, "Foam tender to the threshold");
+            assert_eq!(protocol.contact_numbers, vec!["999".to_string()]);
+            assert_eq!(protocol.evacuation_routes, vec!["North gate".to_string()]);
+            assert_eq!(protocol.revision, 1);
+            assert_eq!(protocol.approved_by, None);
+            assert_eq!(protocol.approved_at, 3 * DAY);
+
+            let old = BaselineEmergencyProtocol { protocol_type: "volcanic ash".to_string(), ..old };
+            let protocol: EmergencyProtocol = decode_record(&baseline_record(&old));
+            assert_eq!(protocol.protocol_type, ProtocolType::Other { name: "volcanic ash".to_string() });
+        }
+
+        #[test]
+        fn baseline_fuel_and_revenue_decode_unarchived() {
+            let old = BaselineFuelInventory {
+                id: 8,
+                airstrip_id: 1,
+                fuel_type: "Jet A-1".to_string(),
+                quantity: 12_500.5,
+                unit_price: 1.25,
+                last_updated: 7 * DAY,
+            };
+            let fuel: FuelInventory = decode_record(&baseline_record(&old));
+            assert_eq!(fuel.id, 8);
+            assert_eq!(fuel.fuel_type, "Jet A-1");
+            assert_eq!(fuel.quantity, 12_500.5);
+            assert_eq!(fuel.unit_price, 1.25);
+            assert_eq!(fuel.last_updated, 7 * DAY);
+            assert_eq!(fuel.archived_at, None);
+
+            let old = BaselineRevenue {
+                id: 9,
+                airstrip_id: 1,
+                source: "landing_fees".to_string(),
+                amount: 320.0,
+                transaction_date: 8 * DAY,
+                description: "March landings".to_string(),
+            };
+            let revenue: Revenue = decode_record(&baseline_record(&old));
+            assert_eq!(revenue.id, 9);
+            assert_eq!(revenue.source, "landing_fees");
+            assert_eq!(revenue.amount, 320.0);
+            assert_eq!(revenue.transaction_date, 8 * DAY);
+            assert_eq!(revenue.description, "March landings");
+            assert_eq!(revenue.archived_at, None);
+        }
+    }
+
+    mod duty_limits {
+        use super::*;
+
+        #[test]
+        fn peak_window_total_of_nothing_is_zero() {
+            assert_eq!(peak_window_total(&[], 100, 0, 1_000), 0);
+        }
+
+        #[test]
+        fn peak_window_total_sums_intervals_that_fit_one_window() {
+            let intervals = [(0, 10), (20, 30)];
+            assert_eq!(peak_window_total(&intervals, 30, 0, 30), 20);
+            // One unit short of covering both, the best period loses one unit of either
+            assert_eq!(peak_window_total(&intervals, 29, 0, 30), 19);
+            // Too far apart to share a window
+            assert_eq!(peak_window_total(&intervals, 10, 0, 30), 10);
+        }
+
+        #[test]
+        fn peak_window_total_only_counts_periods_overlapping_the_new_duty() {
+            let intervals = [(0, 50), (100, 110)];
+            // The 50-unit interval is heavier, but no 20-unit period touching [100, 110) reaches it
+            assert_eq!(peak_window_total(&intervals, 20, 100, 110), 10);
+            assert_eq!(peak_window_total(&intervals, 60, 100, 110), 10);
+            assert_eq!(peak_window_total(&intervals, 70, 100, 110), 10 + 10);
+        }
+
+        #[test]
+        fn peak_window_total_does_not_overflow_near_the_end_of_time() {
+            let intervals = [(u64::MAX - 10, u64::MAX)];
+            assert_eq!(peak_window_total(&intervals, u64::MAX, u64::MAX - 10, u64::MAX), 10);
+        }
+
+        #[test]
+        fn changing_the_limits_is_audited() {
+            as_admin();
+            let before = get_ftl_limits();
+            let limits = FtlLimits { min_rest_minutes: before.min_rest_minutes + 60, ..before.clone() };
+            set_ftl_limits(limits.clone()).unwrap();
+
+            let query = AuditQuery { entity: Some(EntityKind::FtlLimits), ..Default::default() };
+            let page = get_audit_log(query).unwrap();
+            assert_eq!(page.entries.len(), 1);
+            assert_eq!(page.entries[0].before, serde_json::to_string(&before).ok());
+            assert_eq!(page.entries[0].after, serde_json::to_string(&limits).ok());
+        }
     }
 
-    let airstrip_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
+    mod night {
+        use super::*;
+
+        #[test]
+        fn night_runs_from_six_in_the_evening_to_six_in_the_morning() {
+            assert!(is_night(0, 0));
+            assert!(is_night(5 * HOUR + 59 * NANOS_PER_MINUTE, 0));
+            assert!(!is_night(6 * HOUR, 0));
+            assert!(!is_night(17 * HOUR + 59 * NANOS_PER_MINUTE, 0));
+            assert!(is_night(18 * HOUR, 0));
+            // 17:00 UTC is 20:00 local three hours east of Greenwich
+            assert!(is_night(DAY + 17 * HOUR, 3 * HOUR as i64));
+            // 05:00 UTC is 02:00 local three hours west
+            assert!(is_night(DAY + 5 * HOUR, -3 * (HOUR as i64)));
+            assert!(!is_night(DAY + 10 * HOUR, -3 * (HOUR as i64)));
+        }
 
-    let airstrip = Airstrip {
-        id: airstrip_id,
-        name: payload.name,
-        location: payload.location,
-        contact: payload.contact,
-        email: payload.email,
-        runway_length: payload.runway_length,
-        capacity: payload.capacity,
-        created_at: time(),
-    };
+        #[test]
+        fn night_time_counts_only_the_dark_part_of_a_flight() {
+            // Entirely by day
+            assert_eq!(night_time(DAY + 9 * HOUR, DAY + 11 * HOUR, 0), 0);
+            // 17:00 to 19:00 straddles dusk
+            assert_eq!(night_time(DAY + 17 * HOUR, DAY + 19 * HOUR, 0), HOUR);
+            // 05:00 to 07:00 straddles dawn
+            assert_eq!(night_time(DAY + 5 * HOUR, DAY + 7 * HOUR, 0), HOUR);
+        }
 
-    AIRSTRIPS.with(|airstrips| {
-        airstrips.borrow_mut().insert(airstrip_id, airstrip.clone());
-    });
+        #[test]
+        fn night_time_handles_flights_that_cross_midnight() {
+            // 22:00 to 02:00 the next day is all night
+            assert_eq!(night_time(DAY + 22 * HOUR, 2 * DAY + 2 * HOUR, 0), 4 * HOUR);
+            // 16:00 to 08:00 the next day: two hours of day either side of the night
+            assert_eq!(night_time(DAY + 16 * HOUR, 2 * DAY + 8 * HOUR, 0), 12 * HOUR);
+            // 20:00 to 23:00 UTC is 23:00 to 02:00 local three hours east
+            assert_eq!(night_time(DAY + 20 * HOUR, DAY + 23 * HOUR, 3 * HOUR as i64), 3 * HOUR);
+        }
 
-    Ok(airstrip)
-}
+        #[test]
+        fn solar_offset_follows_longitude() {
+            as_admin();
+            let at = |longitude: f64| CreateAirstripPayload {
+                coordinates: Some(Coordinates { longitude, ..Default::default() }),
+                ..airstrip_payload()
+            };
+            let east = new_airstrip(at(37.5));
+            let west = new_airstrip(at(-15.0));
+            let unplaced = new_airstrip(airstrip_payload());
 
-// Schedule Flight
-#[ic_cdk::update]
-fn schedule_flight(payload: ScheduleFlightPayload) -> Result<Flight, Message> {
-    if payload.flight_number.is_empty() || payload.destination.is_empty() {
-        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+            assert_eq!(solar_offset(east.id), (2.5 * HOUR as f64) as i64);
+            assert_eq!(solar_offset(west.id), -(HOUR as i64));
+            assert_eq!(solar_offset(unplaced.id), 0);
+            assert_eq!(solar_offset(u64::MAX), 0);
+        }
     }
 
-    let airstrip_exists = AIRSTRIPS.with(|airstrips| airstrips.borrow().contains_key(&payload.airstrip_id));
-    if !airstrip_exists {
-        return Err(Message::NotFound("Airstrip not found".to_string()));
-    }
+    mod logbook {
+        use super::*;
 
-    let flight_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
+        #[test]
+        fn only_the_pilot_in_command_is_credited_the_takeoff_and_landing() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let flight = new_flight(ScheduleFlightPayload {
+                category: FlightCategory::Training,
+                ..flight_payload(airstrip.id, aircraft.id, 10 * HOUR)
+            });
+            let captain = new_pilot(pilot_payload("Amina"));
+            let first_officer = new_pilot(pilot_payload("Otieno"));
+            schedule_pilot(captain.id, flight.id, 9 * HOUR, 12 * HOUR, CrewRole::PilotInCommand).unwrap();
+            schedule_pilot(first_officer.id, flight.id, 9 * HOUR, 12 * HOUR, CrewRole::SecondInCommand).unwrap();
+            fly(flight.id);
 
-    let flight = Flight {
-        id: flight_id,
-        airstrip_id: payload.airstrip_id,
-        flight_number: payload.flight_number,
-        destination: payload.destination,
-        departure_time: payload.departure_time,
-        arrival_time: payload.arrival_time,
-        status: "scheduled".to_string(),
-    };
+            let recent = get_recency(captain.id, Some(DAY), None).unwrap();
+            assert_eq!((recent.takeoffs, recent.landings), (1, 1));
+            let recent = get_recency(first_officer.id, Some(DAY), None).unwrap();
+            assert_eq!((recent.takeoffs, recent.landings), (0, 0));
 
-    FLIGHTS.with(|flights| {
-        flights.borrow_mut().insert(flight_id, flight.clone());
-    });
+            // Both still log the flight time
+            let totals = get_logbook_totals(first_officer.id).unwrap();
+            assert_eq!((totals.flights, totals.flight_minutes), (1, 60));
+            assert_eq!(totals.day_takeoffs + totals.night_takeoffs, 0);
+        }
 
-    Ok(flight)
-}
+        #[test]
+        fn changing_the_recency_policy_is_audited() {
+            as_admin();
+            let policy = RecencyPolicy { enforce: true, ..Default::default() };
+            set_recency_policy(policy).unwrap();
 
-// Register Pilot
-#[ic_cdk::update]
-fn register_pilot(payload: RegisterPilotPayload) -> Result<Pilot, Message> {
-    if payload.name.is_empty() || payload.license_number.is_empty() {
-        return Err(Message::InvalidPayload("Missing required fields".to_string()));
+            let query = AuditQuery { entity: Some(EntityKind::RecencyPolicy), ..Default::default() };
+            let page = get_audit_log(query).unwrap();
+            assert_eq!(page.entries.len(), 1);
+            assert!(page.entries[0].before.as_ref().unwrap().contains("\"enforce\":false"));
+            assert!(page.entries[0].after.as_ref().unwrap().contains("\"enforce\":true"));
+        }
     }
 
-    let pilot_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
+    mod availability {
+        use super::*;
 
-    let pilot = Pilot {
-        id: pilot_id,
-        name: payload.name,
-        license_number: payload.license_number,
-        experience_years: payload.experience_years,
-        contact: payload.contact,
-        email: payload.email,
-    };
+        fn window(day: Weekday, start_minute: u64, end_minute: u64) -> AvailabilityWindow {
+            AvailabilityWindow { day, start_minute, end_minute }
+        }
 
-    PILOTS.with(|pilots| {
-        pilots.borrow_mut().insert(pilot_id, pilot.clone());
-    });
+        // 2024-01-01, a Monday, at the given UTC hour
+        fn monday_at(hour: u64) -> u64 {
+            JAN_1_2024 * DAY + hour * HOUR
+        }
 
-    Ok(pilot)
-}
+        #[test]
+        fn weekday_counts_from_the_thursday_epoch() {
+            assert_eq!(Weekday::of_day(0), Weekday::Thursday);
+            assert_eq!(Weekday::of_day(3), Weekday::Sunday);
+            assert_eq!(Weekday::of_day(4), Weekday::Monday);
+            assert_eq!(Weekday::of_day(7), Weekday::Thursday);
+            assert_eq!(Weekday::of_day(JAN_1_2024), Weekday::Monday);
+        }
 
-// Schedule Maintenance
-#[ic_cdk::update]
-fn schedule_maintenance(payload: ScheduleMaintenancePayload) -> Result<MaintenanceSchedule, Message> {
-    if payload.description.is_empty() {
-        return Err(Message::InvalidPayload("Missing required fields".to_string()));
-    }
+        #[test]
+        fn weekly_windows_cover_duties_inside_them() {
+            let windows = [window(Weekday::Monday, 8 * 60, 17 * 60)];
+            assert!(weekly_covers(&windows, (monday_at(9), monday_at(12)), 0));
+            assert!(weekly_covers(&windows, (monday_at(8), monday_at(17)), 0));
+            assert!(!weekly_covers(&windows, (monday_at(7), monday_at(12)), 0));
+            assert!(!weekly_covers(&windows, (monday_at(16), monday_at(18)), 0));
+            // Same hours, wrong day
+            assert!(!weekly_covers(&windows, (monday_at(9) + DAY, monday_at(12) + DAY), 0));
+            assert!(!weekly_covers(&[], (monday_at(9), monday_at(12)), 0));
+        }
 
-    let airstrip_exists = AIRSTRIPS.with(|airstrips| airstrips.borrow().contains_key(&payload.airstrip_id));
-    if !airstrip_exists {
-        return Err(Message::NotFound("Airstrip not found".to_string()));
+        #[test]
+        fn weekly_windows_join_across_midnight_and_back_to_back() {
+            let late_monday = window(Weekday::Monday, 22 * 60, MINUTES_PER_DAY);
+            let early_tuesday = window(Weekday::Tuesday, 0, 2 * 60);
+            let duty = (monday_at(23), monday_at(25));
+            assert!(weekly_covers(&[late_monday.clone(), early_tuesday.clone()], duty, 0));
+            assert!(!weekly_covers(&[late_monday], duty, 0));
+            assert!(!weekly_covers(&[early_tuesday], duty, 0));
+
+            // Adjacent windows on one day join up; a one-minute gap does not
+            let joined = [window(Weekday::Monday, 8 * 60, 12 * 60), window(Weekday::Monday, 12 * 60, 17 * 60)];
+            assert!(weekly_covers(&joined, (monday_at(9), monday_at(16)), 0));
+            let gapped = [window(Weekday::Monday, 8 * 60, 12 * 60), window(Weekday::Monday, 12 * 60 + 1, 17 * 60)];
+            assert!(!weekly_covers(&gapped, (monday_at(9), monday_at(16)), 0));
+        }
+
+        #[test]
+        fn weekly_windows_are_in_local_solar_time() {
+            let windows = [window(Weekday::Monday, 22 * 60, MINUTES_PER_DAY)];
+            // 20:00 to 21:00 UTC is 23:00 to midnight three hours east
+            let duty = (monday_at(20), monday_at(21));
+            assert!(weekly_covers(&windows, duty, 3 * HOUR as i64));
+            assert!(!weekly_covers(&windows, duty, 0));
+            // 01:00 UTC Tuesday is still Monday 22:00 three hours west
+            let duty = (monday_at(25), monday_at(26));
+            assert!(weekly_covers(&windows, duty, -3 * (HOUR as i64)));
+        }
     }
 
-    let maintenance_id = ID_COUNTER
-        .with(|counter| {
-            let current_value = *counter.borrow().get();
-            counter.borrow_mut().set(current_value + 1)
-        })
-        .expect("Counter increment failed");
+    mod rostering {
+        use super::*;
 
-    let maintenance = MaintenanceSchedule {
-        id: maintenance_id,
-        airstrip_id: payload.airstrip_id,
-        date: payload.date,
-        description: payload.description,
-        status: "scheduled".to_string(),
-    };
+        #[test]
+        fn a_moved_schedule_does_not_hold_its_own_seat() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let flight = new_flight(flight_payload(airstrip.id, aircraft.id, 10 * HOUR));
+            let pilot = new_pilot(pilot_payload("Amina"));
+            let captain = schedule_pilot(pilot.id, flight.id, 9 * HOUR, 12 * HOUR, CrewRole::PilotInCommand).unwrap();
 
-    MAINTENANCE_SCHEDULES.with(|schedules| {
-        schedules.borrow_mut().insert(maintenance_id, maintenance.clone());
-    });
+            assert_eq!(rostered_complement(flight.id, None).count(CrewRole::PilotInCommand), 1);
+            assert_eq!(rostered_complement(flight.id, Some(captain.id)).count(CrewRole::PilotInCommand), 0);
+        }
 
-    Ok(maintenance)
-}
+        fn roster(from: u64, to: u64, cursor: Option<String>) -> RosterResult {
+            auto_roster(RosterRequest { airstrip_id: None, from, to, commit: false, cursor }).unwrap()
+        }
 
-// Flight Capacity Management
-#[ic_cdk::query]
-fn get_capacity_status(airstrip_id: u64) -> Result<CapacityStatus, Message> {
-    let airstrip = match AIRSTRIPS.with(|airstrips| airstrips.borrow().get(&airstrip_id)) {
-        Some(a) => a,
-        None => return Err(Message::NotFound("Airstrip not found".to_string())),
-    };
+        fn captains(result: &RosterResult, flight_id: u64) -> Vec<u64> {
+            result
+                .assignments
+                .iter()
+                .filter(|schedule| schedule.flight_id == flight_id && schedule.role == CrewRole::PilotInCommand)
+                .map(|schedule| schedule.pilot_id)
+                .collect()
+        }
 
-    let current_time = time();
-    let mut scheduled_arrivals = 0;
-    let mut scheduled_departures = 0;
-    let mut current_occupancy = 0;
+        #[test]
+        fn the_least_loaded_pilot_takes_the_seat() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let busy = new_pilot(pilot_payload("Amina"));
+            let idle = new_pilot(pilot_payload("Baraka"));
+            let crewed = new_flight(flight_payload(airstrip.id, aircraft.id, 10 * HOUR));
+            schedule_pilot(busy.id, crewed.id, 9 * HOUR, 12 * HOUR, CrewRole::PilotInCommand).unwrap();
+            let open = new_flight(flight_payload(airstrip.id, aircraft.id, DAY + 10 * HOUR));
 
-    FLIGHTS.with(|flights| {
-        for (_, flight) in flights.borrow().iter() {
-            if flight.airstrip_id == airstrip_id {
-                if flight.status == "scheduled" {
-                    if flight.arrival_time > current_time {
-                        scheduled_arrivals += 1;
-                    }
-                    if flight.departure_time > current_time {
-                        scheduled_departures += 1;
-                    }
-                } else if flight.status == "arrived" {
-                    current_occupancy += 1;
-                }
-            }
+            let result = roster(0, 2 * DAY, None);
+            assert!(captains(&result, crewed.id).is_empty());
+            assert_eq!(captains(&result, open.id), vec![idle.id]);
         }
-    });
 
-    Ok(CapacityStatus {
-        total_capacity: airstrip.capacity,
-        current_occupancy,
-        scheduled_arrivals,
-        scheduled_departures,
-        available_slots: airstrip.capacity.saturating_sub(current_occupancy + scheduled_arrivals - scheduled_departures),
-    })
-}
+        #[test]
+        fn an_unfilled_seat_reports_each_rejected_candidate() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let unrated = new_pilot(RegisterPilotPayload { type_ratings: Vec::new(), ..pilot_payload("Amina") });
+            let flight = new_flight(flight_payload(airstrip.id, aircraft.id, 10 * HOUR));
 
-// Pilot Scheduling
-#[ic_cdk::update]
-fn schedule_pilot(pilot_id: u64, flight_id: u64, start_time: u64, end_time: u64) -> Result<PilotSchedule, Message> {
-    // Verify pilot exists
-    let pilot_exists = PILOTS.with(|pilots| pilots.borrow().contains_key(&pilot_id));
-    if !pilot_exists {
-        return Err(Message::NotFound("Pilot not found".to_string()));
-    }
+            let result = roster(0, DAY, None);
+            assert!(result.assignments.is_empty());
+            let gap = result.gaps.iter().find(|gap| gap.role == CrewRole::PilotInCommand).unwrap();
+            assert_eq!(gap.flight_id, flight.id);
+            assert_eq!(gap.missing, 1);
+            assert_eq!(gap.rejections.iter().map(|r| r.pilot_id).collect::<Vec<_>>(), vec![unrated.id]);
+        }
 
-    // Verify flight exists
-    let flight_exists = FLIGHTS.with(|flights| flights.borrow().contains_key(&flight_id));
-    if !flight_exists {
-        return Err(Message::NotFound("Flight not found".to_string()));
-    }
+        #[test]
+        fn a_long_horizon_resumes_from_its_cursor() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let flights: Vec<Flight> = (0..=MAX_ROSTER_FLIGHTS as u64)
+                .map(|n| new_flight(flight_payload(airstrip.id, aircraft.id, 10 * HOUR + n * 2 * HOUR)))
+                .collect();
+            let to = 20 * DAY;
 
-    // Check pilot availability
-    let is_available = PILOT_SCHEDULES.with(|schedules| {
-        schedules.borrow().iter().all(|(_, schedule)| {
-            if schedule.pilot_id == pilot_id {
-                !(start_time < schedule.end_time && end_time > schedule.start_time)
-            } else {
-                true
-            }
-        })
-    });
+            let first = roster(0, to, None);
+            let cursor = first.resume_from.unwrap();
+            let last = flights.last().unwrap();
+            assert!(first.gaps.iter().all(|gap| gap.flight_id != last.id));
 
-    if !is_available {
-        return Err(Message::Error("Pilot is not available for this time slot".to_string()));
+            let second = roster(0, to, Some(cursor));
+            assert!(second.resume_from.is_none());
+            assert!(!second.gaps.is_empty());
+            assert!(second.gaps.iter().all(|gap| gap.flight_id == last.id));
+            assert!(matches!(
+                auto_roster(RosterRequest { from: 0, to, cursor: Some("soon".to_string()), ..Default::default() }),
+                Err(Message::InvalidPayload(_))
+            ));
+        }
     }
 
-    let schedule_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter.borrow_mut().set(current_value + 1).unwrap();
-        current_value
-    });
+    mod maintenance {
+        use super::*;
 
-    let schedule = PilotSchedule {
-        id: schedule_id,
-        pilot_id,
-        flight_id,
-        start_time,
-        end_time,
-        status: "scheduled".to_string(),
-    };
+        fn closure(airstrip_id: u64, runway_id: Option<u64>, start: u64, end: u64) -> MaintenanceSchedule {
+            MaintenanceSchedule {
+                airstrip_id,
+                date: start,
+                end_time: end,
+                description: "resurfacing".to_string(),
+                impact: MaintenanceImpact::FullClosure,
+                runway_id,
+                ..Default::default()
+            }
+        }
 
-    PILOT_SCHEDULES.with(|schedules| {
-        schedules.borrow_mut().insert(schedule_id, schedule.clone());
-    });
+        fn plan(recurrence: Recurrence, starts_at: u64) -> MaintenancePlan {
+            MaintenancePlan {
+                id: 1,
+                airstrip_id: 1,
+                name: "Runway inspection".to_string(),
+                description: String::new(),
+                recurrence,
+                starts_at,
+                duration_minutes: 60,
+                impact: MaintenanceImpact::default(),
+                runway_id: None,
+                tasks: Vec::new(),
+                last_due: None,
+                active: true,
+                created_at: 0,
+            }
+        }
 
-    Ok(schedule)
-}
+        #[test]
+        fn closing_the_only_long_runway_blocks_flights_without_a_runway() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let long = new_runway(airstrip.id, "09", 1_800);
+            let short = new_runway(airstrip.id, "18", 600);
+            let heavy = new_aircraft(RegisterAircraftPayload {
+                takeoff_distance_required: 1_200,
+                ..aircraft_payload("5Y-HVY")
+            });
+            let light = new_aircraft(RegisterAircraftPayload {
+                takeoff_distance_required: 500,
+                ..aircraft_payload("5Y-LGT")
+            });
+            let flight = new_flight(flight_payload(airstrip.id, heavy.id, 10 * HOUR));
 
-// Emergency Protocols
-#[ic_cdk::update]
-fn create_emergency_protocol(
-    airstrip_id: u64,
-    protocol_type: String,
-    description: String,
-    contact_numbers: Vec<String>,
-    evacuation_routes: Vec<String>,
-) -> Result<EmergencyProtocol, Message> {
-    let protocol_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter.borrow_mut().set(current_value + 1).unwrap();
-        current_value
-    });
+            let long_closed = closure(airstrip.id, Some(long.id), 9 * HOUR, 11 * HOUR);
+            let conflict = maintenance_conflict(&long_closed, &flight, &heavy, None).unwrap();
+            assert!(conflict.blocking);
 
-    let protocol = EmergencyProtocol {
-        id: protocol_id,
-        airstrip_id,
-        protocol_type,
-        description,
-        contact_numbers,
-        evacuation_routes,
-        created_at: time(),
-    };
+            // A light aircraft can use the short runway, so the closure only warns
+            let conflict = maintenance_conflict(&long_closed, &flight, &light, None).unwrap();
+            assert!(!conflict.blocking);
 
-    EMERGENCY_PROTOCOLS.with(|protocols| {
-        protocols.borrow_mut().insert(protocol_id, protocol.clone());
-    });
+            let short_closed = closure(airstrip.id, Some(short.id), 9 * HOUR, 11 * HOUR);
+            let conflict = maintenance_conflict(&short_closed, &flight, &heavy, None).unwrap();
+            assert!(!conflict.blocking);
 
-    Ok(protocol)
-}
+            // Flights on the other runway are untouched
+            let on_short = new_flight(ScheduleFlightPayload {
+                runway_id: Some(short.id),
+                ..flight_payload(airstrip.id, light.id, 10 * HOUR)
+            });
+            assert!(maintenance_conflict(&long_closed, &on_short, &light, None).is_none());
+        }
 
-// Fuel Management
-#[ic_cdk::update]
-fn update_fuel_inventory(
-    airstrip_id: u64,
-    fuel_type: String,
-    quantity: f64,
-    unit_price: f64,
-) -> Result<FuelInventory, Message> {
-    let inventory_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter.borrow_mut().set(current_value + 1).unwrap();
-        current_value
-    });
+        #[test]
+        fn closures_count_within_the_runway_slot() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let runway = new_runway(airstrip.id, "09", 1_800);
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let flight = new_flight(ScheduleFlightPayload {
+                runway_id: Some(runway.id),
+                ..flight_payload(airstrip.id, aircraft.id, 10 * HOUR)
+            });
+            let slot = RUNWAY_SLOT_MINUTES * NANOS_PER_MINUTE;
 
-    let inventory = FuelInventory {
-        id: inventory_id,
-        airstrip_id,
-        fuel_type,
-        quantity,
-        unit_price,
-        last_updated: time(),
-    };
+            // Work ending shortly before the movement still overlaps its slot
+            let just_before = closure(airstrip.id, Some(runway.id), 8 * HOUR, 10 * HOUR - slot / 2);
+            assert!(maintenance_conflict(&just_before, &flight, &aircraft, None).unwrap().blocking);
+            let just_after = closure(airstrip.id, Some(runway.id), 10 * HOUR + slot / 2, 12 * HOUR);
+            assert!(maintenance_conflict(&just_after, &flight, &aircraft, None).unwrap().blocking);
 
-    FUEL_INVENTORIES.with(|inventories| {
-        inventories.borrow_mut().insert(inventory_id, inventory.clone());
-    });
+            let clear = closure(airstrip.id, Some(runway.id), 8 * HOUR, 10 * HOUR - slot);
+            assert!(maintenance_conflict(&clear, &flight, &aircraft, None).is_none());
+        }
 
-    Ok(inventory)
-}
+        #[test]
+        fn monthly_day_31_falls_on_the_last_day_of_february() {
+            let time_of_day = 8 * HOUR;
+            // After 31 January 2024 comes 29 February, a leap year
+            let jan_31 = (JAN_1_2024 + 30) * DAY + time_of_day;
+            let feb_29 = (JAN_1_2024 + 59) * DAY + time_of_day;
+            assert_eq!(next_monthly(jan_31, 31, time_of_day), Some(feb_29));
+            // and then back to the 31st in March
+            assert_eq!(next_monthly(feb_29, 31, time_of_day), Some((JAN_1_2024 + 90) * DAY + time_of_day));
 
-// Revenue Tracking
-#[ic_cdk::update]
-fn record_revenue(
-    airstrip_id: u64,
-    source: String,
-    amount: f64,
-    description: String,
-) -> Result<Revenue, Message> {
-    let revenue_id = ID_COUNTER.with(|counter| {
-        let current_value = *counter.borrow().get();
-        counter.borrow_mut().set(current_value + 1).unwrap();
-        current_value
-    });
+            let jan_31_2023 = (JAN_1_2023 + 30) * DAY + time_of_day;
+            assert_eq!(next_monthly(jan_31_2023, 31, time_of_day), Some((JAN_1_2023 + 58) * DAY + time_of_day));
+        }
 
-    let revenue = Revenue {
-        id: revenue_id,
-        airstrip_id,
-        source,
-        amount,
-        transaction_date: time(),
-        description,
-    };
+        #[test]
+        fn monthly_rolls_over_from_december_into_january() {
+            let dec_20 = (JAN_1_2023 + 353) * DAY;
+            assert_eq!(next_monthly(dec_20, 15, 0), Some((JAN_1_2024 + 14) * DAY));
+            // Later the same day in December still counts
+            assert_eq!(next_monthly(dec_20, 20, HOUR), Some(dec_20 + HOUR));
+        }
 
-    REVENUES.with(|revenues| {
-        revenues.borrow_mut().insert(revenue_id, revenue.clone());
-    });
+        #[test]
+        fn new_plans_start_at_the_first_occurrence_from_now() {
+            let starts_at = JAN_1_2024 * DAY + 8 * HOUR;
+            let weekly = plan(Recurrence::EveryDays { days: 7 }, starts_at);
+            let until = u64::MAX;
+            assert_eq!(next_occurrence(&weekly, starts_at - DAY, until), Some(starts_at));
+            assert_eq!(next_occurrence(&weekly, starts_at, until), Some(starts_at));
+            // Twenty days in, the next one is the fourth week
+            assert_eq!(next_occurrence(&weekly, starts_at + 20 * DAY, until), Some(starts_at + 21 * DAY));
+            assert_eq!(next_occurrence(&weekly, starts_at + 20 * DAY, starts_at + 20 * DAY), None);
 
-    Ok(revenue)
-}
+            let monthly = plan(Recurrence::MonthlyOnDay { day: 1 }, starts_at);
+            assert_eq!(next_occurrence(&monthly, starts_at, until), Some(starts_at));
+            let feb_1 = (JAN_1_2024 + 31) * DAY + 8 * HOUR;
+            assert_eq!(next_occurrence(&monthly, starts_at + 10 * DAY, until), Some(feb_1));
 
-// Revenue Analysis
-#[ic_cdk::query]
-fn get_revenue_analysis(airstrip_id: u64, start_time: u64, end_time: u64) -> Result<HashMap<String, f64>, Message> {
-    let mut analysis = HashMap::new();
-    let mut total_revenue = 0.0;
+            // Once an instance exists the plan carries on from it
+            let generated = MaintenancePlan { last_due: Some(starts_at), ..weekly };
+            assert_eq!(next_occurrence(&generated, starts_at + 20 * DAY, until), Some(starts_at + 7 * DAY));
+        }
+    }
 
-    REVENUES.with(|revenues| {
-        for (_, revenue) in revenues.borrow().iter() {
-            if revenue.airstrip_id == airstrip_id 
-               && revenue.transaction_date >= start_time 
-               && revenue.transaction_date <= end_time {
-                *analysis.entry(revenue.source.clone()).or_insert(0.0) += revenue.amount;
-                total_revenue += revenue.amount;
-            }
+    mod aircraft {
+        use super::*;
+
+        #[test]
+        fn aircraft_may_not_fly_past_an_hours_limit() {
+            as_admin();
+            let aircraft = new_aircraft(RegisterAircraftPayload {
+                airframe_minutes: 5_900,
+                ..aircraft_payload("5Y-ABC")
+            });
+            let inspection = AddInspectionPayload {
+                kind: InspectionKind::HundredHour,
+                due_airframe_minutes: Some(6_000),
+                ..Default::default()
+            };
+            let aircraft = add_inspection(aircraft.id, inspection).unwrap();
+            assert!(aircraft.check_airworthy(0, 90).is_ok());
+            assert!(aircraft.check_airworthy(0, 100).is_ok());
+            assert!(aircraft.check_airworthy(0, 101).is_err());
+            // Status queries plan no flight, so the aircraft is still airworthy
+            assert!(aircraft_status(aircraft.clone(), 0).airworthy);
+
+            let mut closed = aircraft;
+            closed.inspections[0].closed = true;
+            assert!(closed.check_airworthy(0, 600).is_ok());
         }
-    });
 
-    analysis.insert("total".to_string(), total_revenue);
-    Ok(analysis)
-}
+        #[test]
+        fn a_leg_is_credited_once_with_one_landing() {
+            as_admin();
+            let origin = new_airstrip(airstrip_payload());
+            let destination = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let departure = new_flight(flight_payload(origin.id, aircraft.id, 10 * HOUR));
+            let arrival = new_flight(ScheduleFlightPayload {
+                movement: MovementType::Arrival,
+                ..flight_payload(destination.id, aircraft.id, 10 * HOUR)
+            });
 
-// Query functions for new features
-#[ic_cdk::query]
-fn get_pilot_schedule(pilot_id: u64) -> Vec<PilotSchedule> {
-    PILOT_SCHEDULES.with(|schedules| {
-        schedules
-            .borrow()
-            .iter()
-            .filter(|(_, schedule)| schedule.pilot_id == pilot_id)
-            .map(|(_, schedule)| schedule)
-            .collect()
-    })
-}
+            fly(departure.id);
+            let logged = get_aircraft(aircraft.id).unwrap();
+            assert_eq!((logged.airframe_minutes, logged.engine_minutes, logged.landing_cycles), (60, 60, 1));
 
-#[ic_cdk::query]
-fn get_emergency_protocols(airstrip_id: u64) -> Vec<EmergencyProtocol> {
-    EMERGENCY_PROTOCOLS.with(|protocols| {
-        protocols
-            .borrow()
-            .iter()
-            .filter(|(_, protocol)| protocol.airstrip_id == airstrip_id)
-            .map(|(_, protocol)| protocol)
-            .collect()
-    })
-}
+            // The same leg's arrival record adds nothing more
+            fly(arrival.id);
+            let logged = get_aircraft(aircraft.id).unwrap();
+            assert_eq!((logged.airframe_minutes, logged.landing_cycles), (60, 1));
 
-#[ic_cdk::query]
-fn get_fuel_inventory(airstrip_id: u64) -> Vec<FuelInventory> {
-    FUEL_INVENTORIES.with(|inventory| {
-        inventory
-            .borrow()
-            .iter()
-            .filter(|(_, inv)| inv.airstrip_id == airstrip_id)
-            .map(|(_, inv)| inv)
-            .collect()
-    })
-}
+            // A leg to somewhere not kept here is credited from its departure alone
+            let onward = new_flight(flight_payload(destination.id, aircraft.id, 12 * HOUR));
+            fly(onward.id);
+            let logged = get_aircraft(aircraft.id).unwrap();
+            assert_eq!((logged.airframe_minutes, logged.landing_cycles), (120, 2));
+        }
 
-// Exporting the candid interface
-ic_cdk::export_candid!();
+        #[test]
+        fn a_flight_arriving_before_it_departs_logs_no_time() {
+            as_admin();
+            let airstrip = new_airstrip(airstrip_payload());
+            let aircraft = new_aircraft(aircraft_payload("5Y-ABC"));
+            let flight = new_flight(flight_payload(airstrip.id, aircraft.id, 10 * HOUR));
+            // As a baseline record could hold it
+            let flight = Flight { arrival_time: 9 * HOUR, ..flight };
+            FLIGHTS.with(|flights| flights.borrow_mut().insert(flight.id, flight.clone()));
+
+            fly(flight.id);
+            let logged = get_aircraft(aircraft.id).unwrap();
+            assert_eq!((logged.airframe_minutes, logged.landing_cycles), (0, 1));
+        }
+    }
+}